@@ -0,0 +1,215 @@
+use crate::graphics::{rasterize_triangle, shade_fragment, FrameBuffer, Shading};
+use crate::vertex::Vertex;
+
+/// A translucent sample stored in the A-buffer
+#[derive(Clone, Copy)]
+struct Sample {
+    depth: f64,
+    color: [f64; 4],
+    /// Face the fragment belongs to
+    id: u32,
+}
+
+/// Per-pixel fragment lists for order-independent transparency.
+///
+/// Translucent triangles are collected in any order and resolved per pixel
+/// sorted by depth, so intersecting geometry composites correctly.
+pub struct ABuffer {
+    width: usize,
+    height: usize,
+    fragments: Vec<Vec<Sample>>,
+}
+
+impl ABuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        ABuffer {
+            width,
+            height,
+            fragments: vec![Vec::new(); width * height],
+        }
+    }
+
+    /// Rasterizes a translucent triangle of face `id` into the per-pixel fragment lists
+    pub fn draw_triangle(
+        &mut self,
+        v0: &Vertex,
+        v1: &Vertex,
+        v2: &Vertex,
+        shading: &Shading,
+        base_color: [f64; 4],
+        id: u32,
+    ) {
+        let (width, height) = (self.width, self.height);
+        rasterize_triangle(v0, v1, v2, width, height, |fragment| {
+            let color = shade_fragment(&fragment, shading, base_color);
+            self.fragments[fragment.offset].push(Sample {
+                depth: fragment.depth,
+                color,
+                id,
+            });
+        });
+    }
+
+    /// Sorts each pixel's fragments back to front and blends them over the frame buffer.
+    ///
+    /// Fragments hidden behind opaque geometry already in the frame buffer are discarded,
+    /// and the nearest remaining fragment's face is recorded in the ID buffer.
+    pub fn resolve(&mut self, frame: &mut FrameBuffer) {
+        for (offset, samples) in self.fragments.iter_mut().enumerate() {
+            if samples.is_empty() {
+                continue;
+            }
+            let opaque_depth = frame.depth[offset];
+            samples.retain(|sample| sample.depth < opaque_depth);
+            samples.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+            for sample in samples.iter() {
+                frame.blend_pixel(offset, sample.color);
+            }
+            if let Some(nearest) = samples.last() {
+                frame.ids.record(offset, nearest.depth, nearest.id);
+            }
+            samples.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{to_linear, ToneMapping};
+    use crate::fog::{Fog, FogMode};
+    use crate::graphics::draw_triangle_blended;
+    use crate::math::{Vec2, Vec3};
+    use druid::Color;
+
+    const WIDTH: usize = 16;
+    const HEIGHT: usize = 16;
+    const SHADING: Shading = Shading {
+        light_position: Vec3::new(0.0, 0.0, -10.0),
+        light_intensity: 1.0,
+        fog: Fog {
+            mode: FogMode::Off,
+            color: [0.0; 3],
+            start: 0.0,
+            end: 1.0,
+            density: 0.0,
+        },
+    };
+
+    fn vertex(x: f64, y: f64, z: f64) -> Vertex {
+        Vertex {
+            position: Vec3::new(x, y, z),
+            screen_position: Vec2::new(x, y),
+            normal: Vec3::new(0.0, 0.0, -1.0),
+            w: 1.0,
+        }
+    }
+
+    /// A triangle covering the whole 16x16 frame with depth varying linearly in x
+    fn full_triangle(z_left: f64, z_right: f64) -> [Vertex; 3] {
+        let z_at = |x: f64| z_left + (z_right - z_left) * x / WIDTH as f64;
+        [
+            vertex(0.0, 0.0, z_at(0.0)),
+            vertex(0.0, 40.0, z_at(0.0)),
+            vertex(40.0, 0.0, z_at(40.0)),
+        ]
+    }
+
+    fn render_sorted(triangles: &[([Vertex; 3], Color)]) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(WIDTH, HEIGHT);
+        let mut order: Vec<_> = triangles.iter().collect();
+        order.sort_by(|(a, _), (b, _)| {
+            let depth = |t: &[Vertex; 3]| t.iter().map(|v| v.position.z).sum::<f64>();
+            depth(b).total_cmp(&depth(a))
+        });
+        for (t, color) in order {
+            draw_triangle_blended(
+                &t[0],
+                &t[1],
+                &t[2],
+                &mut frame,
+                &SHADING,
+                to_linear(*color),
+                0,
+            );
+        }
+        frame
+    }
+
+    fn render_abuffer(triangles: &[([Vertex; 3], Color)]) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(WIDTH, HEIGHT);
+        let mut abuffer = ABuffer::new(WIDTH, HEIGHT);
+        for (t, color) in triangles {
+            abuffer.draw_triangle(&t[0], &t[1], &t[2], &SHADING, to_linear(*color), 0);
+        }
+        abuffer.resolve(&mut frame);
+        frame
+    }
+
+    fn pixel(frame: &FrameBuffer, x: usize, y: usize) -> [u8; 4] {
+        let offset = (y * frame.width + x) * 4;
+        let pixels = frame.encode(ToneMapping::Clamp, 1.0);
+        pixels[offset..offset + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn matches_sorted_blending_for_separated_layers() {
+        let triangles = [
+            (full_triangle(1.0, 1.0), Color::rgba8(255, 0, 0, 128)),
+            (full_triangle(3.0, 3.0), Color::rgba8(0, 0, 255, 128)),
+            (full_triangle(2.0, 2.0), Color::rgba8(0, 255, 0, 128)),
+        ];
+        let sorted = render_sorted(&triangles);
+        let abuffer = render_abuffer(&triangles);
+        assert_eq!(sorted.color, abuffer.color);
+    }
+
+    #[test]
+    fn order_is_independent_of_submission() {
+        let red = || (full_triangle(1.0, 1.0), Color::rgba8(255, 0, 0, 128));
+        let blue = || (full_triangle(2.0, 2.0), Color::rgba8(0, 0, 255, 128));
+        let forward = render_abuffer(&[red(), blue()]);
+        let backward = render_abuffer(&[blue(), red()]);
+        assert_eq!(forward.color, backward.color);
+    }
+
+    #[test]
+    fn resolves_intersecting_triangles_per_pixel() {
+        // Red is in front on the left half, blue is in front on the right half
+        let red = (full_triangle(1.0, 3.0), Color::rgba8(255, 0, 0, 128));
+        let blue = (full_triangle(3.0, 1.0), Color::rgba8(0, 0, 255, 128));
+        let triangles = [red, blue];
+        let abuffer = render_abuffer(&triangles);
+
+        let left = pixel(&abuffer, 2, 2);
+        let right = pixel(&abuffer, WIDTH - 3, 2);
+        assert!(
+            left[0] > left[2],
+            "red should dominate on the left: {:?}",
+            left
+        );
+        assert!(
+            right[2] > right[0],
+            "blue should dominate on the right: {:?}",
+            right
+        );
+
+        // Sorting whole triangles can only get one of the two halves right
+        let sorted = render_sorted(&triangles);
+        let sorted_left = pixel(&sorted, 2, 2);
+        let sorted_right = pixel(&sorted, WIDTH - 3, 2);
+        assert!(sorted_left != left || sorted_right != right);
+    }
+
+    #[test]
+    fn discards_fragments_behind_opaque_geometry() {
+        let mut frame = FrameBuffer::new(WIDTH, HEIGHT);
+        frame.depth.iter_mut().for_each(|depth| *depth = 2.0);
+        let mut abuffer = ABuffer::new(WIDTH, HEIGHT);
+        let t = full_triangle(3.0, 3.0);
+        let red = to_linear(Color::rgba8(255, 0, 0, 128));
+        abuffer.draw_triangle(&t[0], &t[1], &t[2], &SHADING, red, 0);
+        abuffer.resolve(&mut frame);
+        assert!(frame.color.iter().all(|&pixel| pixel == [0.0; 4]));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::color::to_linear;
+use crate::graphics::FrameBuffer;
+use druid::{Color, Data, ImageBuf};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// What is drawn behind the scene
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundMode {
+    /// A single color
+    Solid,
+    /// A vertical gradient from the top color to the bottom color
+    Gradient,
+    /// An image scaled to cover the window
+    Image,
+}
+
+impl BackgroundMode {
+    /// Returns the next mode in the cycle
+    pub fn next(self) -> Self {
+        match self {
+            BackgroundMode::Solid => BackgroundMode::Gradient,
+            BackgroundMode::Gradient => BackgroundMode::Image,
+            BackgroundMode::Image => BackgroundMode::Solid,
+        }
+    }
+
+    /// Human-readable name of the mode
+    pub fn name(self) -> &'static str {
+        match self {
+            BackgroundMode::Solid => "Solid",
+            BackgroundMode::Gradient => "Gradient",
+            BackgroundMode::Image => "Image",
+        }
+    }
+}
+
+/// A decoded background image in linear color
+pub struct BackgroundImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f64; 4]>,
+}
+
+impl BackgroundImage {
+    /// Loads and decodes an image file
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let image = ImageBuf::from_file(path)?;
+        let pixels = image.pixel_colors().flatten().map(to_linear).collect();
+        Ok(BackgroundImage {
+            width: image.width(),
+            height: image.height(),
+            pixels,
+        })
+    }
+
+    /// Samples the image so that it covers a frame of the given size, keeping its aspect ratio
+    fn sample(&self, x: usize, y: usize, width: usize, height: usize) -> [f64; 4] {
+        let scale = (width as f64 / self.width as f64).max(height as f64 / self.height as f64);
+        let ix = (x as f64 + 0.5 - width as f64 / 2.0) / scale + self.width as f64 / 2.0;
+        let iy = (y as f64 + 0.5 - height as f64 / 2.0) / scale + self.height as f64 / 2.0;
+        let ix = (ix.max(0.0) as usize).min(self.width - 1);
+        let iy = (iy.max(0.0) as usize).min(self.height - 1);
+        self.pixels[iy * self.width + ix]
+    }
+}
+
+/// Fills the frame buffer with the background.
+///
+/// The image mode falls back to the solid color when no image is available.
+pub fn fill_background(
+    frame: &mut FrameBuffer,
+    mode: BackgroundMode,
+    top: Color,
+    bottom: Color,
+    image: Option<&BackgroundImage>,
+) {
+    let (width, height) = (frame.width, frame.height);
+    let (top, bottom) = (to_linear(top), to_linear(bottom));
+    for y in 0..height {
+        // Interpolate the gradient in linear space
+        let t = (y as f64 + 0.5) / height as f64;
+        let row_color = [
+            top[0] * (1.0 - t) + bottom[0] * t,
+            top[1] * (1.0 - t) + bottom[1] * t,
+            top[2] * (1.0 - t) + bottom[2] * t,
+            top[3] * (1.0 - t) + bottom[3] * t,
+        ];
+        for x in 0..width {
+            let color = match (mode, image) {
+                (BackgroundMode::Gradient, _) => row_color,
+                (BackgroundMode::Image, Some(image)) if image.width > 0 && image.height > 0 => {
+                    image.sample(x, y, width, height)
+                }
+                _ => top,
+            };
+            frame.set_pixel(y * width + x, color);
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::math::{Mat4, Vec3};
+use crate::mesh::BoundingSphere;
+use crate::quaternion::Quat;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+/// Vertical field of view of the perspective projection, in radians
+pub const FIELD_OF_VIEW: f64 = std::f64::consts::FRAC_PI_4;
+/// The world's up direction; screen y grows downwards, so up is -Y
+pub const WORLD_UP: Vec3 = Vec3::new(0.0, -1.0, 0.0);
+/// Default closest distance between the camera and its target
+pub const MIN_DISTANCE: f64 = 2.0;
+/// Default farthest distance between the camera and its target
+pub const MAX_DISTANCE: f64 = 500.0;
+/// Duration of animated camera transitions in seconds
+pub const TRANSITION_DURATION: f64 = 0.5;
+/// Slowest fly camera speed in world units per second
+pub const MIN_FLY_SPEED: f64 = 0.1;
+/// Fastest fly camera speed in world units per second
+pub const MAX_FLY_SPEED: f64 = 50.0;
+
+/// How the mouse and keyboard move the camera
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraMode {
+    /// Orbit, pan and dolly around the target
+    Orbit,
+    /// First-person movement with WASD and mouse look
+    Fly,
+}
+
+impl CameraMode {
+    /// Returns the other mode
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::Orbit,
+        }
+    }
+
+    /// Human-readable name of the mode
+    pub fn name(self) -> &'static str {
+        match self {
+            CameraMode::Orbit => "Orbit",
+            CameraMode::Fly => "Fly",
+        }
+    }
+}
+
+/// How view space is mapped onto the screen
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    /// Distant objects appear smaller
+    Perspective,
+    /// Parallel projection; size does not depend on depth
+    Orthographic,
+}
+
+impl Projection {
+    /// Returns the other projection
+    pub fn next(self) -> Self {
+        match self {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        }
+    }
+
+    /// Human-readable name of the projection
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Perspective => "Perspective",
+            Projection::Orthographic => "Orthographic",
+        }
+    }
+}
+
+/// Viewer looking from `position` at `target`.
+///
+/// View space has x pointing right, y down and z away from the viewer, so
+/// view-space z is the depth used by the rasterizer.
+#[derive(Clone, Copy, Debug, Data, PartialEq, Serialize, Deserialize)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    /// Direction that appears upwards on screen
+    pub up: Vec3,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Vec3::new(0.0, 0.0, -5.0),
+            target: Vec3::ZERO,
+            up: WORLD_UP,
+        }
+    }
+}
+
+impl Camera {
+    /// Camera looking at `target` from `distance` away, rotated by `orientation`.
+    ///
+    /// The identity orientation looks down +z with -y up, like the default camera.
+    pub fn from_orientation(target: Vec3, distance: f64, orientation: Quat) -> Camera {
+        Camera {
+            position: target - orientation.rotate(Vec3::Z) * distance,
+            target,
+            up: -orientation.rotate(Vec3::Y),
+        }
+    }
+
+    /// Rotation from view space to world space
+    pub fn orientation(&self) -> Quat {
+        Quat::from_mat3(self.view_matrix().to_mat3().transpose())
+    }
+
+    /// Camera keeping the current view direction that fits a sphere in view
+    pub fn framing(&self, sphere: BoundingSphere, min_distance: f64, max_distance: f64) -> Camera {
+        // Leave a small margin around the sphere
+        let distance = sphere.radius * 1.1 / (FIELD_OF_VIEW / 2.0).sin();
+        let distance = distance.clamp(min_distance, max_distance);
+        Camera::from_orientation(sphere.center, distance, self.orientation())
+    }
+
+    /// Blends towards `other`: the target moves linearly, the distance geometrically
+    /// and the orientation along the shortest arc
+    pub fn interpolate(&self, other: &Camera, t: f64) -> Camera {
+        let target = self.target.lerp(other.target, t);
+        let distance = self.distance() * (other.distance() / self.distance()).powf(t);
+        let orientation = self.orientation().slerp(other.orientation(), t);
+        Camera::from_orientation(target, distance, orientation)
+    }
+
+    /// Distance from the camera to its target
+    pub fn distance(&self) -> f64 {
+        (self.target - self.position).length()
+    }
+
+    /// Unit vector from the camera towards its target
+    pub fn forward(&self) -> Vec3 {
+        (self.target - self.position).normalize()
+    }
+
+    /// Unit vector pointing to the right on screen
+    pub fn right(&self) -> Vec3 {
+        self.forward().cross(self.up).normalize()
+    }
+
+    /// World-to-view transform
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at(self.position, self.target, self.up)
+    }
+
+    /// Maps a view-space position back to world space
+    pub fn view_to_world(&self, point: Vec3) -> Vec3 {
+        // The view rotation is orthonormal, so its transpose is its inverse
+        self.view_matrix().to_mat3().transpose() * point + self.position
+    }
+
+    /// Orbits the camera around its target so the scene appears rotated by `rotation`.
+    ///
+    /// `rotation` is expressed in view space, as produced by the drag rotation modes.
+    pub fn orbit(&mut self, rotation: Quat) {
+        // Rotating the scene one way is the same as moving the camera the other way
+        let view = self.view_matrix().to_mat3();
+        let world_rotation = view.transpose() * rotation.conjugate().to_mat3() * view;
+        self.position = self.target + world_rotation * (self.position - self.target);
+        self.up = (world_rotation * self.up).normalize();
+    }
+
+    /// Moves the camera and its target by a view-space offset
+    pub fn pan(&mut self, offset: Vec3) {
+        self.translate(self.view_matrix().to_mat3().transpose() * offset);
+    }
+
+    /// Moves the camera and its target by a world-space offset
+    pub fn translate(&mut self, offset: Vec3) {
+        self.position += offset;
+        self.target += offset;
+    }
+
+    /// Turns the camera in place, keeping the horizon level.
+    ///
+    /// Positive `yaw` turns right and positive `pitch` looks up, both in radians.
+    /// Pitch stops short of looking straight up or down.
+    pub fn look(&mut self, yaw: f64, pitch: f64) {
+        let distance = self.distance();
+        let mut forward = Quat::from_axis_angle(WORLD_UP, -yaw).rotate(self.forward());
+        let right = forward.cross(WORLD_UP).normalize();
+        let pitched = Quat::from_axis_angle(right, pitch).rotate(forward);
+        if pitched.dot(WORLD_UP).abs() < 0.99 {
+            forward = pitched;
+        }
+        self.up = WORLD_UP;
+        self.target = self.position + forward * distance;
+    }
+
+    /// Moves the camera towards (`factor` < 1.0) or away from `anchor`.
+    ///
+    /// Camera and target are scaled about the anchor, so it stays at the same spot
+    /// on screen under either projection. The factor is limited so the distance to
+    /// the target stays within `min_distance..=max_distance`; the applied factor is
+    /// returned.
+    pub fn zoom(&mut self, anchor: Vec3, factor: f64, min_distance: f64, max_distance: f64) -> f64 {
+        let distance = self.distance();
+        let factor = (distance * factor).clamp(min_distance, max_distance) / distance;
+        self.position = anchor + (self.position - anchor) * factor;
+        self.target = anchor + (self.target - anchor) * factor;
+        factor
+    }
+}
+
+/// Animated move of the camera from one view to another
+pub struct CameraTransition {
+    from: Camera,
+    to: Camera,
+    /// Start and end orientation of the object, if it turns along with the camera
+    orientation: Option<(Quat, Quat)>,
+    elapsed: f64,
+}
+
+impl CameraTransition {
+    pub fn new(from: Camera, to: Camera) -> Self {
+        CameraTransition {
+            from,
+            to,
+            orientation: None,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Also turns the object from one orientation to another during the transition
+    pub fn with_orientation(mut self, from: Quat, to: Quat) -> Self {
+        self.orientation = Some((from, to));
+        self
+    }
+
+    /// Eased progress from 0.0 to 1.0
+    fn progress(&self) -> f64 {
+        // Ease in and out with a smoothstep
+        let t = (self.elapsed / TRANSITION_DURATION).min(1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    /// Advances the transition by `dt` seconds and returns the camera for this frame
+    pub fn advance(&mut self, dt: f64) -> Camera {
+        self.elapsed += dt;
+        if self.is_finished() {
+            return self.to;
+        }
+        self.from.interpolate(&self.to, self.progress())
+    }
+
+    /// Orientation of the object for the current frame, if the transition turns it
+    pub fn orientation(&self) -> Option<Quat> {
+        self.orientation
+            .map(|(from, to)| from.slerp(to, self.progress()).normalize())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= TRANSITION_DURATION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_view_looks_down_z() {
+        let view = Camera::default().view_matrix();
+        assert_eq!(view.transform_point(Vec3::ZERO), Vec3::new(0.0, 0.0, 5.0));
+        assert_eq!(view.transform_vector(Vec3::X), Vec3::X);
+        assert_eq!(view.transform_vector(Vec3::Y), Vec3::Y);
+    }
+
+    #[test]
+    fn orbit_matches_rotating_the_scene() {
+        let mut camera = Camera::default();
+        let rotation = Quat::from_euler(Vec3::new(0.3, -0.8, 0.2));
+        let point = Vec3::new(0.5, -1.0, 1.0);
+        let before = camera.view_matrix().transform_point(point);
+        camera.orbit(rotation);
+
+        let after = camera.view_matrix().transform_point(point);
+        let target = Vec3::new(0.0, 0.0, 5.0);
+        assert!((after - (target + rotation.rotate(before - target))).length() < 1e-9);
+        assert!((camera.distance() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn look_turns_in_place() {
+        let mut camera = Camera::default();
+        camera.look(0.4, 0.2);
+        assert_eq!(camera.position, Camera::default().position);
+        assert!((camera.distance() - 5.0).abs() < 1e-9);
+        // Turning right moves the view direction towards +x, looking up towards -y
+        assert!(camera.forward().x > 0.0 && camera.forward().y < 0.0);
+        assert!(camera.right().dot(WORLD_UP).abs() < 1e-9);
+    }
+
+    #[test]
+    fn orientation_round_trips() {
+        let mut camera = Camera::default();
+        camera.orbit(Quat::from_euler(Vec3::new(0.9, -0.3, 1.7)));
+        camera.pan(Vec3::new(0.5, 1.0, 0.0));
+        let rebuilt =
+            Camera::from_orientation(camera.target, camera.distance(), camera.orientation());
+        assert!((rebuilt.position - camera.position).length() < 1e-9);
+        assert!((rebuilt.up - camera.up).length() < 1e-9);
+        assert_eq!(
+            Camera::from_orientation(Vec3::ZERO, 5.0, Quat::IDENTITY),
+            Camera::default()
+        );
+    }
+
+    #[test]
+    fn framing_fits_sphere() {
+        let sphere = BoundingSphere {
+            center: Vec3::new(1.0, 2.0, 3.0),
+            radius: 4.0,
+        };
+        let camera = Camera::default().framing(sphere, MIN_DISTANCE, MAX_DISTANCE);
+        assert_eq!(camera.target, sphere.center);
+        assert_eq!(camera.forward(), Camera::default().forward());
+        // The sphere's silhouette stays inside the field of view
+        assert!(sphere.radius / camera.distance() < (FIELD_OF_VIEW / 2.0).sin());
+    }
+
+    #[test]
+    fn transition_ends_at_destination() {
+        let from = Camera::default();
+        let mut to = Camera::default();
+        to.orbit(Quat::from_axis_angle(Vec3::Y, 2.0));
+        to.zoom(Vec3::ZERO, 3.0, MIN_DISTANCE, MAX_DISTANCE);
+        let mut transition = CameraTransition::new(from, to);
+        let halfway = transition.advance(TRANSITION_DURATION / 2.0);
+        assert!(halfway.distance() > 5.0 && halfway.distance() < 15.0);
+        assert!(!transition.is_finished());
+        assert_eq!(transition.advance(TRANSITION_DURATION), to);
+        assert!(transition.is_finished());
+    }
+
+    #[test]
+    fn zoom_keeps_anchor_in_place() {
+        let mut camera = Camera::default();
+        camera.orbit(Quat::from_euler(Vec3::new(0.2, 0.5, 0.0)));
+        let anchor = Vec3::new(0.6, -0.4, -1.0);
+        let before = camera.view_matrix().transform_point(anchor);
+        camera.zoom(anchor, 0.5, MIN_DISTANCE, MAX_DISTANCE);
+
+        // Same direction from the camera, at half the depth
+        let after = camera.view_matrix().transform_point(anchor);
+        assert!((after - before * 0.5).length() < 1e-9);
+        assert!((camera.distance() - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn zoom_respects_limits() {
+        let mut camera = Camera::default();
+        assert_eq!(camera.zoom(Vec3::ZERO, 0.01, 2.0, 10.0), 0.4);
+        assert_eq!(camera.distance(), 2.0);
+        camera.zoom(Vec3::ZERO, 1000.0, 2.0, 10.0);
+        assert_eq!(camera.distance(), 10.0);
+    }
+
+    #[test]
+    fn view_to_world_inverts_view() {
+        let mut camera = Camera::default();
+        camera.orbit(Quat::from_euler(Vec3::new(-0.7, 0.1, 0.4)));
+        let point = Vec3::new(1.0, 2.0, -3.0);
+        let view = camera.view_matrix().transform_point(point);
+        assert!((camera.view_to_world(view) - point).length() < 1e-9);
+    }
+}
@@ -0,0 +1,225 @@
+use druid::{Color, Data};
+use serde::{Deserialize, Serialize};
+
+/// Converts an sRGB-encoded channel (0.0 - 1.0) to linear light
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear light channel (0.0 - 1.0) to sRGB encoding
+pub fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts a color to linear, non-premultiplied RGBA
+pub fn to_linear(color: Color) -> [f64; 4] {
+    let (r, g, b, a) = color.as_rgba();
+    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+}
+
+/// Lowest exposure reachable with the keys, the panel or the settings file
+pub const MIN_EXPOSURE: f64 = 0.05;
+/// Highest exposure reachable with the keys, the panel or the settings file
+pub const MAX_EXPOSURE: f64 = 20.0;
+
+/// Operator used to map HDR radiance into the displayable range
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapping {
+    /// Scale by exposure and clip at 1.0
+    Clamp,
+    /// Reinhard operator `c / (1 + c)`
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+    /// Exponential exposure operator `1 - exp(-c)`
+    Exposure,
+}
+
+impl ToneMapping {
+    /// Returns the next operator in the cycle
+    pub fn next(self) -> Self {
+        match self {
+            ToneMapping::Clamp => ToneMapping::Reinhard,
+            ToneMapping::Reinhard => ToneMapping::Aces,
+            ToneMapping::Aces => ToneMapping::Exposure,
+            ToneMapping::Exposure => ToneMapping::Clamp,
+        }
+    }
+
+    /// Human-readable name of the operator
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMapping::Clamp => "Clamp",
+            ToneMapping::Reinhard => "Reinhard",
+            ToneMapping::Aces => "ACES",
+            ToneMapping::Exposure => "Exposure",
+        }
+    }
+
+    /// Maps a linear HDR channel to the 0.0 - 1.0 range
+    pub fn apply(self, c: f64, exposure: f64) -> f64 {
+        let c = c.max(0.0) * exposure;
+        let mapped = match self {
+            ToneMapping::Clamp => c,
+            ToneMapping::Reinhard => c / (1.0 + c),
+            ToneMapping::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+            ToneMapping::Exposure => 1.0 - (-c).exp(),
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+/// Preset colors for the six faces of the cube
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    /// Saturated red, green, blue, yellow, magenta and cyan
+    Default,
+    /// Light, desaturated versions of the default colors
+    Pastel,
+    /// Six evenly spaced shades of gray
+    Grayscale,
+    /// Okabe and Ito's palette, distinguishable with color vision deficiencies
+    OkabeIto,
+}
+
+impl Palette {
+    /// Every palette, in cycle order
+    pub const ALL: [Palette; 4] = [
+        Palette::Default,
+        Palette::Pastel,
+        Palette::Grayscale,
+        Palette::OkabeIto,
+    ];
+
+    /// Returns the next palette in the cycle
+    pub fn next(self) -> Self {
+        match self {
+            Palette::Default => Palette::Pastel,
+            Palette::Pastel => Palette::Grayscale,
+            Palette::Grayscale => Palette::OkabeIto,
+            Palette::OkabeIto => Palette::Default,
+        }
+    }
+
+    /// Human-readable name of the palette
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Default => "Default",
+            Palette::Pastel => "Pastel",
+            Palette::Grayscale => "Grayscale",
+            Palette::OkabeIto => "Okabe-Ito",
+        }
+    }
+
+    /// Face colors of the palette, in face order
+    pub const fn colors(self) -> [Color; 6] {
+        match self {
+            Palette::Default => [
+                Color::rgb8(255, 0, 0),   // Red
+                Color::rgb8(0, 255, 0),   // Green
+                Color::rgb8(0, 0, 255),   // Blue
+                Color::rgb8(255, 255, 0), // Yellow
+                Color::rgb8(255, 0, 255), // Magenta
+                Color::rgb8(0, 255, 255), // Cyan
+            ],
+            Palette::Pastel => [
+                Color::rgb8(255, 179, 186), // Pink
+                Color::rgb8(186, 255, 201), // Mint
+                Color::rgb8(186, 225, 255), // Baby blue
+                Color::rgb8(255, 255, 186), // Cream
+                Color::rgb8(224, 187, 228), // Lavender
+                Color::rgb8(181, 234, 234), // Light teal
+            ],
+            Palette::Grayscale => [
+                Color::rgb8(230, 230, 230),
+                Color::rgb8(195, 195, 195),
+                Color::rgb8(160, 160, 160),
+                Color::rgb8(125, 125, 125),
+                Color::rgb8(90, 90, 90),
+                Color::rgb8(55, 55, 55),
+            ],
+            Palette::OkabeIto => [
+                Color::rgb8(213, 94, 0),    // Vermillion
+                Color::rgb8(0, 158, 115),   // Bluish green
+                Color::rgb8(0, 114, 178),   // Blue
+                Color::rgb8(240, 228, 66),  // Yellow
+                Color::rgb8(204, 121, 167), // Reddish purple
+                Color::rgb8(86, 180, 233),  // Sky blue
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TONE_MAPPINGS: [ToneMapping; 4] = [
+        ToneMapping::Clamp,
+        ToneMapping::Reinhard,
+        ToneMapping::Aces,
+        ToneMapping::Exposure,
+    ];
+
+    #[test]
+    fn srgb_round_trips_through_linear() {
+        for i in 0..=255 {
+            let c = i as f64 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-12);
+            assert!((srgb_to_linear(linear_to_srgb(c)) - c).abs() < 1e-12);
+        }
+        // Both pieces of the curve meet at the threshold
+        assert!((srgb_to_linear(0.04045) - 0.0031308).abs() < 1e-7);
+        assert!(srgb_to_linear(0.5) < 0.5);
+        assert_eq!(srgb_to_linear(1.0), 1.0);
+    }
+
+    #[test]
+    fn tone_mapping_curves_rise_within_the_display_range() {
+        for tone_mapping in TONE_MAPPINGS {
+            assert_eq!(tone_mapping.apply(0.0, 1.0), 0.0, "{}", tone_mapping.name());
+            assert_eq!(
+                tone_mapping.apply(-1.0, 1.0),
+                0.0,
+                "{}",
+                tone_mapping.name()
+            );
+            let mut previous = 0.0;
+            for i in 1..=100 {
+                let mapped = tone_mapping.apply(i as f64 * 0.2, 1.0);
+                assert!(
+                    mapped >= previous && mapped <= 1.0,
+                    "{}",
+                    tone_mapping.name()
+                );
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn tone_mapping_curves_match_their_formulas() {
+        assert_eq!(ToneMapping::Clamp.apply(0.5, 1.0), 0.5);
+        assert_eq!(ToneMapping::Clamp.apply(3.0, 1.0), 1.0);
+        assert_eq!(ToneMapping::Reinhard.apply(1.0, 1.0), 0.5);
+        assert!((ToneMapping::Exposure.apply(1.0, 1.0) - (1.0 - (-1.0f64).exp())).abs() < 1e-12);
+        // The ACES fit maps mid-gray slightly brighter and approaches white
+        assert!((ToneMapping::Aces.apply(0.18, 1.0) - 0.2666).abs() < 1e-3);
+        assert!(ToneMapping::Aces.apply(100.0, 1.0) > 0.99);
+        // Exposure scales the input before the curve
+        assert_eq!(
+            ToneMapping::Reinhard.apply(0.5, 2.0),
+            ToneMapping::Reinhard.apply(1.0, 1.0)
+        );
+    }
+}
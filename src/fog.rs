@@ -0,0 +1,108 @@
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+/// How fog density grows with depth
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FogMode {
+    /// No fog
+    Off,
+    /// Fog ramps linearly from the start to the end depth
+    Linear,
+    /// Fog thickens exponentially beyond the start depth
+    Exponential,
+}
+
+impl FogMode {
+    /// Returns the next mode in the cycle
+    pub fn next(self) -> Self {
+        match self {
+            FogMode::Off => FogMode::Linear,
+            FogMode::Linear => FogMode::Exponential,
+            FogMode::Exponential => FogMode::Off,
+        }
+    }
+
+    /// Human-readable name of the mode
+    pub fn name(self) -> &'static str {
+        match self {
+            FogMode::Off => "Off",
+            FogMode::Linear => "Linear",
+            FogMode::Exponential => "Exponential",
+        }
+    }
+}
+
+/// Depth fog blended into fragments as they are shaded
+#[derive(Clone, Copy)]
+pub struct Fog {
+    pub mode: FogMode,
+    /// Linear fog color
+    pub color: [f64; 3],
+    /// Depth at which fog begins
+    pub start: f64,
+    /// Depth at which linear fog becomes opaque
+    pub end: f64,
+    /// Density of exponential fog per unit of depth
+    pub density: f64,
+}
+
+impl Fog {
+    /// Fraction of the fog color mixed into a fragment at the given depth (0.0 - 1.0)
+    pub fn factor(&self, depth: f64) -> f64 {
+        match self.mode {
+            FogMode::Off => 0.0,
+            FogMode::Linear => {
+                let range = (self.end - self.start).max(f64::EPSILON);
+                ((depth - self.start) / range).clamp(0.0, 1.0)
+            }
+            FogMode::Exponential => 1.0 - (-self.density * (depth - self.start).max(0.0)).exp(),
+        }
+    }
+
+    /// Blends the fog color into a linear, non-premultiplied RGBA color
+    pub fn apply(&self, color: [f64; 4], depth: f64) -> [f64; 4] {
+        let f = self.factor(depth);
+        [
+            color[0] * (1.0 - f) + self.color[0] * f,
+            color[1] * (1.0 - f) + self.color[1] * f,
+            color[2] * (1.0 - f) + self.color[2] * f,
+            color[3],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fog(mode: FogMode) -> Fog {
+        Fog {
+            mode,
+            color: [1.0, 1.0, 1.0],
+            start: 2.0,
+            end: 6.0,
+            density: 0.5,
+        }
+    }
+
+    #[test]
+    fn linear_fog_ramps_from_start_to_end() {
+        let linear = fog(FogMode::Linear);
+        assert_eq!(linear.factor(1.0), 0.0);
+        assert_eq!(linear.factor(4.0), 0.5);
+        assert_eq!(linear.factor(10.0), 1.0);
+        assert_eq!(fog(FogMode::Off).factor(10.0), 0.0);
+    }
+
+    #[test]
+    fn exponential_fog_thickens_beyond_start() {
+        let exponential = fog(FogMode::Exponential);
+        assert_eq!(exponential.factor(2.0), 0.0);
+        assert!((exponential.factor(4.0) - (1.0 - (-1.0f64).exp())).abs() < 1e-12);
+        // Alpha is kept while the color moves towards the fog color
+        let fogged = exponential.apply([0.0, 0.0, 0.0, 0.5], 4.0);
+        assert_eq!(fogged[3], 0.5);
+        assert!(fogged[0] > 0.0 && fogged[0] < 1.0);
+    }
+}
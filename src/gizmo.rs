@@ -0,0 +1,305 @@
+use crate::camera::{Camera, WORLD_UP};
+use crate::math::{Vec2, Vec3};
+use crate::quaternion::Quat;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::{PI, TAU};
+
+/// World axes the gizmo handles are aligned with, in X, Y, Z order
+pub const AXES: [Vec3; 3] = [Vec3::X, Vec3::Y, Vec3::Z];
+/// On-screen length of the axis arrows and radius of the rings, in pixels
+pub const GIZMO_SIZE: f64 = 80.0;
+/// Pixels the cursor may be away from a handle and still grab it
+const HANDLE_TOLERANCE: f64 = 6.0;
+/// Half the side of the square uniform scale handle, in pixels
+const SCALE_HANDLE_SIZE: f64 = 8.0;
+/// Line segments used to draw each rotation ring
+const RING_SEGMENTS: usize = 48;
+/// Natural log of the scale factor per pixel dragged on the scale handle
+const SCALE_SENSITIVITY: f64 = 0.01;
+/// Radius of the sun disc marking the light, in pixels
+pub const LIGHT_SIZE: f64 = 7.0;
+/// Radians the light orbits per pixel dragged
+const LIGHT_ORBIT_SENSITIVITY: f64 = 0.01;
+
+/// Which manipulator is shown over the cube
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GizmoMode {
+    Off,
+    /// Arrows moving the cube along the world axes
+    Translate,
+    /// Rings rotating the cube around the world axes
+    Rotate,
+    /// Handle scaling the cube uniformly
+    Scale,
+}
+
+impl GizmoMode {
+    /// Returns the next mode in the cycle
+    pub fn next(self) -> Self {
+        match self {
+            GizmoMode::Off => GizmoMode::Translate,
+            GizmoMode::Translate => GizmoMode::Rotate,
+            GizmoMode::Rotate => GizmoMode::Scale,
+            GizmoMode::Scale => GizmoMode::Off,
+        }
+    }
+
+    /// Human-readable name of the mode
+    pub fn name(self) -> &'static str {
+        match self {
+            GizmoMode::Off => "Off",
+            GizmoMode::Translate => "Translate",
+            GizmoMode::Rotate => "Rotate",
+            GizmoMode::Scale => "Scale",
+        }
+    }
+
+    /// Handle that a drag constrained to world axis `axis` (0 to 2) manipulates
+    pub fn constrained_handle(self, axis: usize) -> Option<Handle> {
+        match self {
+            GizmoMode::Off => None,
+            GizmoMode::Translate => Some(Handle::Axis(axis)),
+            GizmoMode::Rotate => Some(Handle::Ring(axis)),
+            // Scaling is uniform, so there is no axis to constrain it to
+            GizmoMode::Scale => Some(Handle::Scale),
+        }
+    }
+}
+
+/// Part of a gizmo that can be dragged
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Handle {
+    /// Arrow along a world axis
+    Axis(usize),
+    /// Ring around a world axis
+    Ring(usize),
+    /// Uniform scale square at the center
+    Scale,
+}
+
+/// Change to the cube's transform produced by dragging a handle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GizmoEdit {
+    /// World-space offset
+    Translate(Vec3),
+    /// World-space rotation around the cube's center
+    Rotate(Quat),
+    /// Uniform scale factor
+    Scale(f64),
+}
+
+/// A gizmo laid out on screen
+pub struct Gizmo {
+    /// Screen position of the gizmo's origin
+    center: Vec2,
+    /// Screen displacement per world unit along each axis
+    axis_screen: [Vec2; 3],
+    /// Whether each axis points away from the viewer
+    axis_away: [bool; 3],
+    /// Screen-space polylines of the handles of the current mode
+    handles: Vec<(Handle, Vec<Vec2>)>,
+}
+
+impl Gizmo {
+    /// Lays out the gizmo of `mode` at a world-space `origin`.
+    ///
+    /// `length` is the world-space length of the axes and radius of the rings and
+    /// `project` maps world positions to the screen and their view depth, or `None`
+    /// if they are behind the viewer. Returns `None` if the gizmo is not visible.
+    pub fn new(
+        mode: GizmoMode,
+        origin: Vec3,
+        length: f64,
+        project: impl Fn(Vec3) -> Option<(Vec2, f64)>,
+    ) -> Option<Gizmo> {
+        if mode == GizmoMode::Off {
+            return None;
+        }
+        let (center, depth) = project(origin)?;
+        let mut axis_screen = [Vec2::ZERO; 3];
+        let mut axis_away = [false; 3];
+        let mut ends = [Vec2::ZERO; 3];
+        for (i, &axis) in AXES.iter().enumerate() {
+            let (end, end_depth) = project(origin + axis * length)?;
+            axis_screen[i] = (end - center) / length;
+            axis_away[i] = end_depth > depth;
+            ends[i] = end;
+        }
+
+        let handles = match mode {
+            GizmoMode::Off => Vec::new(),
+            GizmoMode::Translate => (0..3)
+                .map(|i| (Handle::Axis(i), vec![center, ends[i]]))
+                .collect(),
+            GizmoMode::Rotate => (0..3)
+                .map(|i| {
+                    // Circle in the plane of the other two axes
+                    let (u, v) = (AXES[(i + 1) % 3], AXES[(i + 2) % 3]);
+                    let ring = (0..=RING_SEGMENTS)
+                        .filter_map(|segment| {
+                            let angle = segment as f64 / RING_SEGMENTS as f64 * TAU;
+                            let point = origin + (u * angle.cos() + v * angle.sin()) * length;
+                            project(point).map(|(screen, _)| screen)
+                        })
+                        .collect();
+                    (Handle::Ring(i), ring)
+                })
+                .collect(),
+            GizmoMode::Scale => {
+                let s = SCALE_HANDLE_SIZE;
+                let square = [(-s, -s), (s, -s), (s, s), (-s, s), (-s, -s)]
+                    .map(|(x, y)| center + Vec2::new(x, y))
+                    .to_vec();
+                vec![(Handle::Scale, square)]
+            }
+        };
+
+        Some(Gizmo {
+            center,
+            axis_screen,
+            axis_away,
+            handles,
+        })
+    }
+
+    /// Screen-space polylines of the handles, for drawing
+    pub fn handles(&self) -> &[(Handle, Vec<Vec2>)] {
+        &self.handles
+    }
+
+    /// Returns the handle under a screen point, if any
+    pub fn handle_at(&self, point: Vec2) -> Option<Handle> {
+        self.handles
+            .iter()
+            .filter_map(|(handle, polyline)| {
+                let distance = match handle {
+                    // The scale handle is a filled square
+                    Handle::Scale => {
+                        let offset = point - self.center;
+                        (offset.x.abs().max(offset.y.abs()) - SCALE_HANDLE_SIZE).max(0.0)
+                    }
+                    _ => polyline
+                        .windows(2)
+                        .map(|segment| distance_to_segment(point, segment[0], segment[1]))
+                        .fold(f64::INFINITY, f64::min),
+                };
+                (distance <= HANDLE_TOLERANCE).then_some((*handle, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(handle, _)| handle)
+    }
+
+    /// Converts a mouse drag from `from` to `to` on `handle` into a transform change
+    pub fn drag(&self, handle: Handle, from: Vec2, to: Vec2) -> GizmoEdit {
+        match handle {
+            Handle::Axis(i) => {
+                // Follow the component of the mouse movement along the projected axis
+                let direction = self.axis_screen[i];
+                let length_squared = direction.length_squared();
+                if length_squared < 1e-12 {
+                    return GizmoEdit::Translate(Vec3::ZERO);
+                }
+                let distance = (to - from).dot(direction) / length_squared;
+                GizmoEdit::Translate(AXES[i] * distance)
+            }
+            Handle::Ring(i) => {
+                // Turn by the angle the mouse swept around the center
+                let (a, b) = (from - self.center, to - self.center);
+                let mut angle = b.y.atan2(b.x) - a.y.atan2(a.x);
+                if angle > PI {
+                    angle -= TAU;
+                } else if angle < -PI {
+                    angle += TAU;
+                }
+                // Screen y points down, so positive screen angles turn clockwise, which
+                // is a positive rotation around an axis pointing away from the viewer
+                let angle = if self.axis_away[i] { angle } else { -angle };
+                GizmoEdit::Rotate(Quat::from_axis_angle(AXES[i], angle))
+            }
+            Handle::Scale => {
+                // Dragging right or up grows the cube
+                let amount = (to.x - from.x) - (to.y - from.y);
+                GizmoEdit::Scale((amount * SCALE_SENSITIVITY).exp())
+            }
+        }
+    }
+}
+
+/// Orbits the light around `center` for a mouse drag of `delta` pixels.
+///
+/// Horizontal drags swing it around the world's up axis and vertical drags over
+/// the top or bottom, so the light follows the mouse as seen through `camera`.
+pub fn orbit_light(light: Vec3, center: Vec3, camera: &Camera, delta: Vec2) -> Vec3 {
+    let swing = Quat::from_axis_angle(WORLD_UP, delta.x * LIGHT_ORBIT_SENSITIVITY);
+    let tilt = Quat::from_axis_angle(camera.right(), delta.y * LIGHT_ORBIT_SENSITIVITY);
+    center + (tilt * swing).rotate(light - center)
+}
+
+/// Distance from a point to the segment `ab`
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f64 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    if length_squared == 0.0 {
+        return (point - a).length();
+    }
+    let t = ((point - a).dot(ab) / length_squared).clamp(0.0, 1.0);
+    (point - (a + ab * t)).length()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Perspective view from (0, 0, -5) looking down +z with 100 pixels per unit at depth 1
+    fn project(point: Vec3) -> Option<(Vec2, f64)> {
+        let depth = point.z + 5.0;
+        (depth > 0.0).then(|| (Vec2::new(point.x, point.y) * (100.0 / depth), depth))
+    }
+
+    #[test]
+    fn axis_drag_follows_projected_axis() {
+        let gizmo = Gizmo::new(GizmoMode::Translate, Vec3::ZERO, 1.0, project).unwrap();
+        // One world unit along X spans 20 pixels at depth 5
+        assert_eq!(gizmo.handle_at(Vec2::new(10.0, 1.0)), Some(Handle::Axis(0)));
+        assert_eq!(gizmo.handle_at(Vec2::new(1.0, 10.0)), Some(Handle::Axis(1)));
+        assert_eq!(gizmo.handle_at(Vec2::new(30.0, 30.0)), None);
+        let edit = gizmo.drag(Handle::Axis(0), Vec2::new(10.0, 0.0), Vec2::new(20.0, 7.0));
+        assert_eq!(edit, GizmoEdit::Translate(Vec3::new(0.5, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn ring_drag_turns_with_the_mouse() {
+        let gizmo = Gizmo::new(GizmoMode::Rotate, Vec3::ZERO, 1.0, project).unwrap();
+        // The Z ring faces the viewer as a circle of 20 pixels radius, while the
+        // X and Y rings are seen edge-on as vertical and horizontal lines
+        let diagonal = 20.0 * std::f64::consts::FRAC_1_SQRT_2;
+        assert_eq!(
+            gizmo.handle_at(Vec2::new(diagonal, diagonal)),
+            Some(Handle::Ring(2))
+        );
+        // A quarter turn clockwise on screen moves X onto Y, as seen on screen
+        let GizmoEdit::Rotate(rotation) =
+            gizmo.drag(Handle::Ring(2), Vec2::new(20.0, 0.0), Vec2::new(0.0, 20.0))
+        else {
+            panic!("ring drags rotate");
+        };
+        assert!((rotation.rotate(Vec3::X) - Vec3::Y).length() < 1e-9);
+    }
+
+    #[test]
+    fn light_follows_the_mouse_around_the_center() {
+        let camera = Camera::default();
+        let center = Vec3::new(0.0, 0.0, 1.0);
+        // A light between the camera and the center
+        let light = Vec3::new(0.0, 0.0, -2.0);
+        let right = orbit_light(light, center, &camera, Vec2::new(10.0, 0.0));
+        let up = orbit_light(light, center, &camera, Vec2::new(0.0, -10.0));
+        assert!(right.dot(camera.right()) > 0.0);
+        assert!(up.dot(WORLD_UP) > 0.0);
+        for moved in [right, up] {
+            assert!(((moved - center).length() - 3.0).abs() < 1e-9);
+        }
+    }
+}
@@ -1,122 +1,389 @@
-use crate::math::{apply_lighting, calculate_light_intensity, edge_function};
+use crate::camera::Projection;
+use crate::color::{linear_to_srgb, ToneMapping};
+use crate::fog::Fog;
+use crate::idbuffer::IdBuffer;
+use crate::math::{
+    apply_lighting, calculate_light_intensity, edge_function, Mat4, Vec2, Vec3, AMBIENT_INTENSITY,
+};
 use crate::vertex::Vertex;
-use druid::Color;
 
-/// Draws a triangle with per-pixel lighting
-pub fn draw_triangle(
+/// Color and depth targets the rasterizer draws into
+pub struct FrameBuffer {
+    pub width: usize,
+    pub height: usize,
+    /// HDR accumulation buffer of linear, premultiplied RGBA
+    pub color: Vec<[f64; 4]>,
+    /// Depth of the nearest opaque surface per pixel
+    pub depth: Vec<f64>,
+    /// Linear ambient radiance of the nearest opaque surface, used by ambient occlusion
+    pub ambient: Vec<[f64; 3]>,
+    /// Face IDs of the nearest surface, opaque or not, written alongside the depth
+    pub ids: IdBuffer,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        FrameBuffer {
+            width,
+            height,
+            color: vec![[0.0; 4]; width * height],
+            depth: vec![f64::INFINITY; width * height],
+            ambient: vec![[0.0; 3]; width * height],
+            ids: IdBuffer::new(width, height),
+        }
+    }
+
+    /// Replaces the pixel at `offset` with a linear, non-premultiplied RGBA color
+    pub fn set_pixel(&mut self, offset: usize, color: [f64; 4]) {
+        let a = color[3];
+        self.color[offset] = [color[0] * a, color[1] * a, color[2] * a, a];
+    }
+
+    /// Composites a linear, non-premultiplied RGBA color over the pixel at `offset`
+    pub fn blend_pixel(&mut self, offset: usize, color: [f64; 4]) {
+        let src_a = color[3];
+        let dst = &mut self.color[offset];
+        for i in 0..3 {
+            dst[i] = color[i] * src_a + dst[i] * (1.0 - src_a);
+        }
+        dst[3] = src_a + dst[3] * (1.0 - src_a);
+    }
+
+    /// Tone maps the HDR buffer and encodes it as non-premultiplied sRGB RGBA8
+    pub fn encode(&self, tone_mapping: ToneMapping, exposure: f64) -> Vec<u8> {
+        let mut pixels = vec![0u8; self.width * self.height * 4];
+        for (pixel, color) in pixels.chunks_exact_mut(4).zip(self.color.iter()) {
+            let a = color[3];
+            if a <= 0.0 {
+                continue;
+            }
+            for i in 0..3 {
+                let mapped = tone_mapping.apply(color[i] / a, exposure);
+                pixel[i] = (linear_to_srgb(mapped) * 255.0).round() as u8;
+            }
+            pixel[3] = (a.min(1.0) * 255.0).round() as u8;
+        }
+        pixels
+    }
+}
+
+/// Maps view-space positions to screen pixels and back
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    /// Projection followed by the mapping to pixels. The projected z is the reciprocal
+    /// depth under perspective and the depth itself under orthographic projection.
+    pub transform: Mat4,
+    /// Inverse of `transform`
+    pub inverse: Mat4,
+    pub projection: Projection,
+}
+
+impl Viewport {
+    /// Viewport centered on `center` with `scale` pixels per view-space unit.
+    ///
+    /// Under perspective the scale applies at a depth of 1.0 and shrinks with distance.
+    pub fn new(center: Vec2, scale: f64, projection: Projection) -> Self {
+        let projection_matrix = match projection {
+            Projection::Perspective => Mat4::perspective(),
+            Projection::Orthographic => Mat4::IDENTITY,
+        };
+        let transform = Mat4::translation(center.extend(0.0))
+            * Mat4::scale(Vec3::new(scale, scale, 1.0))
+            * projection_matrix;
+        Viewport {
+            transform,
+            inverse: transform.inverse().unwrap_or(Mat4::IDENTITY),
+            projection,
+        }
+    }
+
+    /// Homogeneous w of a view-space position: its depth under perspective, 1.0 otherwise
+    pub fn w(&self, position: Vec3) -> f64 {
+        match self.projection {
+            Projection::Perspective => position.z,
+            Projection::Orthographic => 1.0,
+        }
+    }
+
+    /// Projects a view-space position to screen coordinates
+    pub fn project(&self, position: Vec3) -> Vec2 {
+        self.transform.transform_point(position).truncate()
+    }
+
+    /// Reconstructs the view-space position of a screen point at the given depth
+    pub fn unproject(&self, screen: Vec2, depth: f64) -> Vec3 {
+        let projected_depth = match self.projection {
+            Projection::Perspective => 1.0 / depth,
+            Projection::Orthographic => depth,
+        };
+        let position = self.inverse.transform_point(screen.extend(projected_depth));
+        Vec3::new(position.x, position.y, depth)
+    }
+}
+
+/// Scene-wide inputs used when shading fragments
+#[derive(Clone, Copy)]
+pub struct Shading {
+    /// Light position in view space
+    pub light_position: Vec3,
+    /// Brightness of the light, scaling its diffuse contribution
+    pub light_intensity: f64,
+    /// Depth fog blended into every shaded fragment
+    pub fog: Fog,
+}
+
+/// A shaded sample produced by rasterizing a triangle
+pub struct Fragment {
+    /// Pixel offset (y * width + x)
+    pub offset: usize,
+    /// Interpolated depth
+    pub depth: f64,
+    /// Interpolated position
+    pub position: Vec3,
+    /// Interpolated, normalized normal
+    pub normal: Vec3,
+}
+
+/// View-space depth in front of which triangles are clipped
+pub const NEAR_PLANE: f64 = 0.05;
+
+/// Point where the edge from `a` to `b` crosses the near plane
+fn near_plane_intersection(a: &Vertex, b: &Vertex) -> Vertex {
+    let t = (NEAR_PLANE - a.position.z) / (b.position.z - a.position.z);
+    let w = a.w + (b.w - a.w) * t;
+    // Screen coordinates times w are linear along the edge, like the view-space position
+    let screen =
+        (a.screen_position * a.w) + (b.screen_position * b.w - a.screen_position * a.w) * t;
+    Vertex {
+        position: a.position + (b.position - a.position) * t,
+        screen_position: screen * (1.0 / w),
+        normal: a.normal + (b.normal - a.normal) * t,
+        w,
+    }
+}
+
+/// Screen endpoints of the part of a line segment in front of the near plane, if any
+pub fn clip_line_near(a: &Vertex, b: &Vertex) -> Option<(Vec2, Vec2)> {
+    match (a.position.z >= NEAR_PLANE, b.position.z >= NEAR_PLANE) {
+        (true, true) => Some((a.screen_position, b.screen_position)),
+        (true, false) => Some((
+            a.screen_position,
+            near_plane_intersection(a, b).screen_position,
+        )),
+        (false, true) => Some((
+            near_plane_intersection(a, b).screen_position,
+            b.screen_position,
+        )),
+        (false, false) => None,
+    }
+}
+
+/// Clips a triangle to the part in front of the near plane, as a fan of 0 to 2 triangles
+fn clip_near(vertices: [&Vertex; 3]) -> Vec<[Vertex; 3]> {
+    let mut polygon = Vec::with_capacity(4);
+    for (i, &current) in vertices.iter().enumerate() {
+        let next = vertices[(i + 1) % 3];
+        let current_inside = current.position.z >= NEAR_PLANE;
+        if current_inside {
+            polygon.push(*current);
+        }
+        if current_inside != (next.position.z >= NEAR_PLANE) {
+            polygon.push(near_plane_intersection(current, next));
+        }
+    }
+    (1..polygon.len().saturating_sub(1))
+        .map(|i| [polygon[0], polygon[i], polygon[i + 1]])
+        .collect()
+}
+
+/// Walks every pixel covered by a triangle and hands the interpolated fragment to `shade`.
+///
+/// Parts of the triangle behind the near plane are clipped away first.
+pub fn rasterize_triangle<F>(
     v0: &Vertex,
     v1: &Vertex,
     v2: &Vertex,
-    pixel_data: &mut [u8],
-    z_buffer: &mut [f64],
     width: usize,
     height: usize,
-    light_pos_world: &[f64; 3],
-    base_color: Color,
-) {
+    mut shade: F,
+) where
+    F: FnMut(Fragment),
+{
+    if [v0, v1, v2].iter().all(|v| v.position.z >= NEAR_PLANE) {
+        rasterize_clipped(v0, v1, v2, width, height, &mut shade);
+    } else {
+        for [c0, c1, c2] in clip_near([v0, v1, v2]) {
+            rasterize_clipped(&c0, &c1, &c2, width, height, &mut shade);
+        }
+    }
+}
+
+/// Rasterizes a triangle lying entirely in front of the near plane
+fn rasterize_clipped<F>(
+    v0: &Vertex,
+    v1: &Vertex,
+    v2: &Vertex,
+    width: usize,
+    height: usize,
+    shade: &mut F,
+) where
+    F: FnMut(Fragment),
+{
+    let (p0, p1, p2) = (v0.screen_position, v1.screen_position, v2.screen_position);
+
     // Compute bounding box of the triangle
-    let min_x = v0
-        .screen_position[0]
-        .min(v1.screen_position[0])
-        .min(v2.screen_position[0])
-        .floor()
-        .max(0.0) as usize;
-    let max_x = v0
-        .screen_position[0]
-        .max(v1.screen_position[0])
-        .max(v2.screen_position[0])
-        .ceil()
-        .min(width as f64 - 1.0) as usize;
-    let min_y = v0
-        .screen_position[1]
-        .min(v1.screen_position[1])
-        .min(v2.screen_position[1])
-        .floor()
-        .max(0.0) as usize;
-    let max_y = v0
-        .screen_position[1]
-        .max(v1.screen_position[1])
-        .max(v2.screen_position[1])
-        .ceil()
-        .min(height as f64 - 1.0) as usize;
-
-    // Precompute area of the triangle
-    let area = edge_function(&v0.screen_position, &v1.screen_position, &v2.screen_position);
+    let min_x = p0.x.min(p1.x).min(p2.x).floor().max(0.0) as usize;
+    let max_x = p0.x.max(p1.x).max(p2.x).ceil().min(width as f64 - 1.0) as usize;
+    let min_y = p0.y.min(p1.y).min(p2.y).floor().max(0.0) as usize;
+    let max_y = p0.y.max(p1.y).max(p2.y).ceil().min(height as f64 - 1.0) as usize;
+
+    // Back-facing and degenerate triangles cover no pixels
+    if edge_function(p0, p1, p2) <= 0.0 {
+        return;
+    }
 
     // For each pixel in the bounding box
     for y in min_y..=max_y {
         for x in min_x..=max_x {
-            let px = x as f64 + 0.5;
-            let py = y as f64 + 0.5;
-            let p = [px, py];
+            let p = Vec2::new(x as f64 + 0.5, y as f64 + 0.5);
 
-            let w0 = edge_function(&v1.screen_position, &v2.screen_position, &p);
-            let w1 = edge_function(&v2.screen_position, &v0.screen_position, &p);
-            let w2 = edge_function(&v0.screen_position, &v1.screen_position, &p);
+            let w0 = edge_function(p1, p2, p);
+            let w1 = edge_function(p2, p0, p);
+            let w2 = edge_function(p0, p1, p);
 
             if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
                 // Inside triangle
-                // Normalize barycentric coordinates
-                let w0 = w0 / area;
-                let w1 = w1 / area;
-                let w2 = w2 / area;
-
-                // Interpolate position
-                let px3d = v0.position[0] * w0 + v1.position[0] * w1 + v2.position[0] * w2;
-                let py3d = v0.position[1] * w0 + v1.position[1] * w1 + v2.position[1] * w2;
-                let pz3d = v0.position[2] * w0 + v1.position[2] * w1 + v2.position[2] * w2;
-
-                // Depth test
-                let offset = y * width + x;
-                if pz3d < z_buffer[offset] {
-                    z_buffer[offset] = pz3d;
-
-                    // Interpolate normal
-                    let nx = v0.normal[0] * w0 + v1.normal[0] * w1 + v2.normal[0] * w2;
-                    let ny = v0.normal[1] * w0 + v1.normal[1] * w1 + v2.normal[1] * w2;
-                    let nz = v0.normal[2] * w0 + v1.normal[2] * w1 + v2.normal[2] * w2;
-                    let length = (nx * nx + ny * ny + nz * nz).sqrt();
-                    let interpolated_normal = [nx / length, ny / length, nz / length];
-
-                    // Compute lighting
-                    let light_intensity = calculate_light_intensity(
-                        &interpolated_normal,
-                        &[px3d, py3d, pz3d],
-                        light_pos_world,
-                    );
-
-                    // Compute shaded color
-                    let shaded_color = apply_lighting(base_color.clone(), light_intensity);
-
-                    // Set pixel color
-                    let pixel_offset = offset * 4;
-                    let (r, g, b, a) = shaded_color.as_rgba8();
-                    pixel_data[pixel_offset] = r;
-                    pixel_data[pixel_offset + 1] = g;
-                    pixel_data[pixel_offset + 2] = b;
-                    pixel_data[pixel_offset + 3] = a;
-                }
+                // Perspective-correct barycentric coordinates
+                let w0 = w0 / v0.w;
+                let w1 = w1 / v1.w;
+                let w2 = w2 / v2.w;
+                let sum = w0 + w1 + w2;
+                let w0 = w0 / sum;
+                let w1 = w1 / sum;
+                let w2 = w2 / sum;
+
+                // Interpolate position and normal
+                let position = v0.position * w0 + v1.position * w1 + v2.position * w2;
+                let normal = v0.normal * w0 + v1.normal * w1 + v2.normal * w2;
+
+                shade(Fragment {
+                    offset: y * width + x,
+                    depth: position.z,
+                    position,
+                    normal: normal.normalize(),
+                });
             }
         }
     }
 }
 
-/// Draws a line between two points in the pixel buffer using Bresenham's algorithm
-pub fn draw_line(
-    x0: f64,
-    y0: f64,
-    x1: f64,
-    y1: f64,
-    pixel_data: &mut [u8],
-    width: usize,
-    height: usize,
-    color: Color,
+/// Computes the lit and fogged, linear, non-premultiplied RGBA color of a fragment
+pub fn shade_fragment(fragment: &Fragment, shading: &Shading, base_color: [f64; 4]) -> [f64; 4] {
+    let light_intensity = calculate_light_intensity(
+        fragment.normal,
+        fragment.position,
+        shading.light_position,
+        shading.light_intensity,
+    );
+    let lit_color = apply_lighting(base_color, light_intensity);
+    shading.fog.apply(lit_color, fragment.depth)
+}
+
+/// Reorders a triangle's vertex indices so it faces the viewer.
+///
+/// The rasterizer culls back faces, so translucent modes flip back-facing
+/// triangles to keep the far side of the mesh visible through the front.
+pub fn front_facing(vertices: &[Vertex], [a, b, c]: [usize; 3]) -> [usize; 3] {
+    let area = edge_function(
+        vertices[a].screen_position,
+        vertices[b].screen_position,
+        vertices[c].screen_position,
+    );
+    if area < 0.0 {
+        [a, c, b]
+    } else {
+        [a, b, c]
+    }
+}
+
+/// Blends `color` over the encoded RGBA8 pixels marked in `mask`
+pub fn overlay_mask(pixels: &mut [u8], mask: &[bool], (r, g, b, a): (u8, u8, u8, u8)) {
+    let alpha = a as f64 / 255.0;
+    for (pixel, _) in pixels.chunks_exact_mut(4).zip(mask).filter(|(_, &m)| m) {
+        for (channel, value) in pixel.iter_mut().zip([r, g, b]) {
+            *channel = (*channel as f64 * (1.0 - alpha) + value as f64 * alpha).round() as u8;
+        }
+    }
+}
+
+/// Draws an opaque triangle of face `id` with per-pixel lighting
+pub fn draw_triangle(
+    v0: &Vertex,
+    v1: &Vertex,
+    v2: &Vertex,
+    frame: &mut FrameBuffer,
+    shading: &Shading,
+    base_color: [f64; 4],
+    id: u32,
 ) {
+    let (width, height) = (frame.width, frame.height);
+    rasterize_triangle(v0, v1, v2, width, height, |fragment| {
+        // Depth test
+        if fragment.depth < frame.depth[fragment.offset] {
+            frame.depth[fragment.offset] = fragment.depth;
+            frame.ids.record(fragment.offset, fragment.depth, id);
+
+            // Compute shaded color
+            let shaded_color = shade_fragment(&fragment, shading, base_color);
+
+            // Set pixel color
+            frame.set_pixel(
+                fragment.offset,
+                [shaded_color[0], shaded_color[1], shaded_color[2], 1.0],
+            );
+            // Fog hides the same share of the ambient term as of the rest of the light
+            let visibility = 1.0 - shading.fog.factor(fragment.depth);
+            frame.ambient[fragment.offset] = [
+                base_color[0] * AMBIENT_INTENSITY * visibility,
+                base_color[1] * AMBIENT_INTENSITY * visibility,
+                base_color[2] * AMBIENT_INTENSITY * visibility,
+            ];
+        }
+    });
+}
+
+/// Draws a translucent triangle of face `id` by blending it over the frame buffer.
+///
+/// Triangles must be submitted back to front for the result to be correct.
+/// The depth buffer is tested against but never written; the ID buffer keeps
+/// the nearest translucent face.
+pub fn draw_triangle_blended(
+    v0: &Vertex,
+    v1: &Vertex,
+    v2: &Vertex,
+    frame: &mut FrameBuffer,
+    shading: &Shading,
+    base_color: [f64; 4],
+    id: u32,
+) {
+    let (width, height) = (frame.width, frame.height);
+    rasterize_triangle(v0, v1, v2, width, height, |fragment| {
+        if fragment.depth < frame.depth[fragment.offset] {
+            frame.ids.record(fragment.offset, fragment.depth, id);
+            let color = shade_fragment(&fragment, shading, base_color);
+            frame.blend_pixel(fragment.offset, color);
+        }
+    });
+}
+
+/// Draws a line between two points in the pixel buffer using Bresenham's algorithm
+pub fn draw_line(start: Vec2, end: Vec2, frame: &mut FrameBuffer, color: [f64; 4]) {
+    let (width, height) = (frame.width, frame.height);
     let (mut x0, mut y0, x1, y1) = (
-        x0.round() as isize,
-        y0.round() as isize,
-        x1.round() as isize,
-        y1.round() as isize,
+        start.x.round() as isize,
+        start.y.round() as isize,
+        end.x.round() as isize,
+        end.y.round() as isize,
     );
     let dx = (x1 - x0).abs();
     let dy = -(y1 - y0).abs();
@@ -126,12 +393,7 @@ pub fn draw_line(
 
     loop {
         if x0 >= 0 && x0 < width as isize && y0 >= 0 && y0 < height as isize {
-            let offset = (y0 as usize * width + x0 as usize) * 4;
-            let (r, g, b, a) = color.as_rgba8();
-            pixel_data[offset] = r;
-            pixel_data[offset + 1] = g;
-            pixel_data[offset + 2] = b;
-            pixel_data[offset + 3] = a;
+            frame.set_pixel(y0 as usize * width + x0 as usize, color);
         }
 
         if x0 == x1 && y0 == y1 {
@@ -148,3 +410,46 @@ pub fn draw_line(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(viewport: &Viewport, position: Vec3) -> Vertex {
+        Vertex {
+            position,
+            screen_position: viewport.project(position),
+            normal: Vec3::new(0.0, 0.0, -1.0),
+            w: viewport.w(position),
+        }
+    }
+
+    #[test]
+    fn clipped_vertices_project_onto_the_original_edges() {
+        let viewport = Viewport::new(Vec2::new(50.0, 50.0), 100.0, Projection::Perspective);
+        let a = vertex(&viewport, Vec3::new(-1.0, 0.5, -1.0));
+        let b = vertex(&viewport, Vec3::new(1.0, -0.5, 3.0));
+        let clipped = near_plane_intersection(&a, &b);
+        assert!((clipped.position.z - NEAR_PLANE).abs() < 1e-12);
+        let expected = viewport.project(clipped.position);
+        assert!((clipped.screen_position - expected).length() < 1e-9);
+        assert!((clipped.w - NEAR_PLANE).abs() < 1e-12);
+    }
+
+    #[test]
+    fn triangles_crossing_the_near_plane_are_clipped_not_dropped() {
+        let viewport = Viewport::new(Vec2::new(50.0, 50.0), 10.0, Projection::Perspective);
+        // One corner is behind the viewer, as when flying into a face
+        let v0 = vertex(&viewport, Vec3::new(-2.0, -2.0, 1.0));
+        let v1 = vertex(&viewport, Vec3::new(0.0, 3.0, -1.0));
+        let v2 = vertex(&viewport, Vec3::new(2.0, -2.0, 1.0));
+        assert_eq!(clip_near([&v0, &v1, &v2]).len(), 2);
+
+        let mut depths = Vec::new();
+        rasterize_triangle(&v0, &v1, &v2, 100, 100, |fragment| {
+            depths.push(fragment.depth)
+        });
+        assert!(!depths.is_empty());
+        assert!(depths.iter().all(|&depth| depth >= NEAR_PLANE - 1e-9));
+    }
+}
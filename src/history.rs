@@ -0,0 +1,271 @@
+use crate::background::BackgroundMode;
+use crate::camera::{Camera, CameraMode, Projection};
+use crate::color::{Palette, ToneMapping};
+use crate::fog::FogMode;
+use crate::gizmo::GizmoMode;
+use crate::math::Vec3;
+use crate::postprocess::PostEffect;
+use crate::quaternion::Quat;
+use crate::rotation::RotationMode;
+use crate::selection::SelectionMode;
+use crate::state::{AppState, TransparencyMode};
+use crate::views::Bookmark;
+use druid::Color;
+use std::sync::Arc;
+
+/// Most edits kept for undoing; older ones are dropped
+const MAX_HISTORY: usize = 100;
+
+/// Declares [`Snapshot`] with the undoable fields of [`AppState`]
+macro_rules! snapshot {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        /// Undoable part of the application state
+        #[derive(Clone, PartialEq)]
+        pub struct Snapshot {
+            $($field: $ty),*
+        }
+
+        impl Snapshot {
+            pub fn capture(data: &AppState) -> Snapshot {
+                Snapshot {
+                    $($field: data.$field.clone()),*
+                }
+            }
+
+            /// Sets the fields that differ between `from` and `to` to their value in `to`.
+            ///
+            /// Fields an edit did not touch keep their current value, so undoing a
+            /// camera move does not also rewind the cube's rotation since then.
+            fn apply(from: &Snapshot, to: &Snapshot, data: &mut AppState) {
+                $(
+                    if from.$field != to.$field {
+                        data.$field = to.$field.clone();
+                    }
+                )*
+            }
+        }
+    };
+}
+
+snapshot! {
+    orientation: Quat,
+    position: Vec3,
+    scale: f64,
+    gizmo: GizmoMode,
+    camera: Camera,
+    projection: Projection,
+    camera_mode: CameraMode,
+    fly_speed: f64,
+    zoom_smoothing: f64,
+    selection_mode: SelectionMode,
+    selection: Arc<Vec<usize>>,
+    bookmarks: Arc<Vec<Bookmark>>,
+    rotation_mode: RotationMode,
+    angular_velocity: Vec3,
+    wireframe: bool,
+    light_position: Vec3,
+    light_intensity: f64,
+    palette: Palette,
+    face_colors: [Color; 6],
+    transparency: TransparencyMode,
+    opacity: f64,
+    tone_mapping: ToneMapping,
+    exposure: f64,
+    post_effects: Arc<Vec<PostEffect>>,
+    ssao: bool,
+    ssao_radius: f64,
+    ssao_strength: f64,
+    background: BackgroundMode,
+    background_color: Color,
+    background_color_bottom: Color,
+    fog: FogMode,
+    fog_color: Color,
+    fog_start: f64,
+    fog_end: f64,
+    fog_density: f64,
+}
+
+/// An edit recorded as the state before and after it
+struct Edit {
+    before: Snapshot,
+    after: Snapshot,
+}
+
+/// Undo and redo stacks of edits.
+///
+/// This is a memento history rather than a command stack: edits are whole-state
+/// snapshots taken around the change, not inverse operations. Every action, drag and
+/// panel slider changes the state through the same fields, so comparing snapshots
+/// records them all without each one knowing how to undo itself; the cost is a copy
+/// of the undoable state, a few hundred bytes, per edit.
+///
+/// An edit spans everything between [`History::begin`] and [`History::end`], so a
+/// whole drag or animated camera move is undone in one step.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// State at the start of the edit in progress
+    pending: Option<Snapshot>,
+}
+
+impl History {
+    /// Starts an edit, unless one is already in progress
+    pub fn begin(&mut self, data: &AppState) {
+        self.begin_with(Snapshot::capture(data));
+    }
+
+    /// Starts an edit from a snapshot taken earlier, unless one is already in progress
+    pub fn begin_with(&mut self, before: Snapshot) {
+        if self.pending.is_none() {
+            self.pending = Some(before);
+        }
+    }
+
+    /// Keeps the automatic rotation of the cube out of the edit in progress
+    pub fn spin(&mut self, rotation: Quat) {
+        if let Some(pending) = &mut self.pending {
+            // Must match AppState::rotate exactly for untouched orientations to compare equal
+            pending.orientation = (rotation * pending.orientation).normalize();
+        }
+    }
+
+    /// Finishes the edit in progress, recording it if it changed anything
+    pub fn end(&mut self, data: &AppState) {
+        let Some(before) = self.pending.take() else {
+            return;
+        };
+        let after = Snapshot::capture(data);
+        if before != after {
+            self.undo.push(Edit { before, after });
+            if self.undo.len() > MAX_HISTORY {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+    }
+
+    /// Reverts the last edit; returns whether there was one
+    pub fn undo(&mut self, data: &mut AppState) -> bool {
+        self.end(data);
+        let Some(edit) = self.undo.pop() else {
+            return false;
+        };
+        Snapshot::apply(&edit.after, &edit.before, data);
+        self.redo.push(edit);
+        true
+    }
+
+    /// Reapplies the last undone edit; returns whether there was one
+    pub fn redo(&mut self, data: &mut AppState) -> bool {
+        self.end(data);
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        Snapshot::apply(&edit.before, &edit.after, data);
+        self.undo.push(edit);
+        true
+    }
+
+    /// Number of edits that can be undone and redone
+    pub fn len(&self) -> (usize, usize) {
+        (self.undo.len(), self.redo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_and_redo_restore_edits() {
+        let mut data = AppState::default();
+        let mut history = History::default();
+
+        history.begin(&data);
+        data.wireframe = true;
+        history.end(&data);
+        // A drag is a single edit however many steps it takes
+        history.begin(&data);
+        for _ in 0..10 {
+            data.position += Vec3::X;
+            history.begin(&data);
+        }
+        history.end(&data);
+        // Edits that change nothing are not recorded
+        history.begin(&data);
+        history.end(&data);
+        assert_eq!(history.len(), (2, 0));
+
+        assert!(history.undo(&mut data));
+        assert_eq!(data.position, Vec3::ZERO);
+        assert!(data.wireframe);
+        assert!(history.undo(&mut data));
+        assert!(!data.wireframe);
+        assert!(!history.undo(&mut data));
+
+        assert!(history.redo(&mut data));
+        assert!(data.wireframe);
+        // A new edit discards the undone ones
+        history.begin(&data);
+        data.scale = 2.0;
+        history.end(&data);
+        assert_eq!(history.len(), (2, 0));
+    }
+
+    #[test]
+    fn undoing_a_palette_switch_restores_edited_colors() {
+        let mut data = AppState::default();
+        let mut history = History::default();
+        data.face_colors[2] = Color::WHITE;
+        let edited = data.face_colors;
+
+        history.begin(&data);
+        data.set_palette(Palette::OkabeIto);
+        history.end(&data);
+        assert_eq!(data.face_colors, Palette::OkabeIto.colors());
+
+        assert!(history.undo(&mut data));
+        assert!(data.palette == Palette::Default);
+        assert_eq!(data.face_colors, edited);
+    }
+
+    #[test]
+    fn panel_sliders_are_undoable() {
+        let mut data = AppState::default();
+        let mut history = History::default();
+        let before = Snapshot::capture(&data);
+
+        history.begin(&data);
+        data.fog_color = Color::BLACK;
+        data.fog_start = 1.0;
+        data.fog_end = 2.0;
+        data.fog_density = 3.0;
+        data.ssao_radius = 2.0;
+        data.ssao_strength = 0.25;
+        data.zoom_smoothing = 0.0;
+        history.end(&data);
+
+        assert!(history.undo(&mut data));
+        assert!(Snapshot::capture(&data) == before);
+    }
+
+    #[test]
+    fn automatic_rotation_is_not_recorded() {
+        let mut data = AppState::default();
+        let mut history = History::default();
+        let spin = Quat::from_axis_angle(Vec3::Y, 0.1);
+
+        history.begin(&data);
+        data.camera.translate(Vec3::X);
+        data.rotate(spin);
+        history.spin(spin);
+        history.end(&data);
+        data.rotate(spin);
+
+        let orientation = data.orientation;
+        assert!(history.undo(&mut data));
+        assert_eq!(data.camera, Camera::default());
+        assert_eq!(data.orientation, orientation);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::graphics::rasterize_triangle;
+use crate::vertex::Vertex;
+
+/// Marks pixels not covered by any face
+const NO_ID: u32 = u32::MAX;
+
+/// Per-pixel face IDs of the nearest surface, for pixel-exact picking and outlines.
+///
+/// Written by the main rasterization pass alongside the depth buffer. Wireframe
+/// rendering has no fill pass, so it fills the buffer with `draw_triangle` instead.
+pub struct IdBuffer {
+    pub width: usize,
+    pub height: usize,
+    ids: Vec<u32>,
+    depth: Vec<f64>,
+}
+
+impl IdBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        IdBuffer {
+            width,
+            height,
+            ids: vec![NO_ID; width * height],
+            depth: vec![f64::INFINITY; width * height],
+        }
+    }
+
+    /// Tags the pixel at `offset` with `id` if the surface at `depth` is the nearest so far
+    pub fn record(&mut self, offset: usize, depth: f64, id: u32) {
+        if depth < self.depth[offset] {
+            self.depth[offset] = depth;
+            self.ids[offset] = id;
+        }
+    }
+
+    /// Rasterizes a triangle, tagging the pixels where it is nearest with `id`
+    pub fn draw_triangle(&mut self, v0: &Vertex, v1: &Vertex, v2: &Vertex, id: u32) {
+        let (width, height) = (self.width, self.height);
+        rasterize_triangle(v0, v1, v2, width, height, |fragment| {
+            self.record(fragment.offset, fragment.depth, id);
+        });
+    }
+
+    /// ID and depth of the surface covering a pixel, if any
+    pub fn get(&self, x: usize, y: usize) -> Option<(u32, f64)> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let offset = y * self.width + x;
+        (self.ids[offset] != NO_ID).then(|| (self.ids[offset], self.depth[offset]))
+    }
+
+    /// Marks the visible border of the area covered by any of `elements`, grown by `width` pixels.
+    ///
+    /// `element_at` maps a covered pixel, with its face ID and depth, to the element shown
+    /// there. A pixel is on the border when a neighbouring pixel shows another face or
+    /// nothing, so hidden elements and the inner edges of a face get no outline.
+    pub fn outline(
+        &self,
+        elements: &[usize],
+        width: usize,
+        element_at: impl Fn(usize, usize, u32, f64) -> Option<usize>,
+    ) -> Vec<bool> {
+        let mut border = vec![false; self.width * self.height];
+        if elements.is_empty() {
+            return border;
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let offset = y * self.width + x;
+                let id = self.ids[offset];
+                if id == NO_ID || !self.on_face_border(x, y) {
+                    continue;
+                }
+                if let Some(element) = element_at(x, y, id, self.depth[offset]) {
+                    border[offset] = elements.contains(&element);
+                }
+            }
+        }
+        (0..width).fold(border, |border, _| self.grow(&border))
+    }
+
+    /// Whether a 4-neighbour of a covered pixel shows another face or nothing
+    fn on_face_border(&self, x: usize, y: usize) -> bool {
+        let id = self.ids[y * self.width + x];
+        let neighbours = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        neighbours
+            .iter()
+            .any(|&(nx, ny)| self.get(nx, ny).map(|(other, _)| other) != Some(id))
+    }
+
+    /// Mask with every pixel next to a marked one marked as well
+    fn grow(&self, mask: &[bool]) -> Vec<bool> {
+        let mut grown = mask.to_vec();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !mask[y * self.width + x] {
+                    continue;
+                }
+                for ny in y.saturating_sub(1)..(y + 2).min(self.height) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(self.width) {
+                        grown[ny * self.width + nx] = true;
+                    }
+                }
+            }
+        }
+        grown
+    }
+
+    /// Encodes the IDs as RGBA8 with a distinct color per ID and black where empty
+    pub fn visualize(&self) -> Vec<u8> {
+        let mut pixels = vec![0u8; self.width * self.height * 4];
+        for (pixel, &id) in pixels.chunks_exact_mut(4).zip(self.ids.iter()) {
+            pixel[3] = 255;
+            if id == NO_ID {
+                continue;
+            }
+            // Spread consecutive IDs around the hue circle by the golden ratio
+            let hue = (id as f64 * 0.618_033_988_75).fract() * 6.0;
+            let x = 1.0 - (hue % 2.0 - 1.0).abs();
+            let rgb = match hue as usize {
+                0 => [1.0, x, 0.0],
+                1 => [x, 1.0, 0.0],
+                2 => [0.0, 1.0, x],
+                3 => [0.0, x, 1.0],
+                4 => [x, 0.0, 1.0],
+                _ => [1.0, 0.0, x],
+            };
+            for i in 0..3 {
+                pixel[i] = (rgb[i] * 255.0).round() as u8;
+            }
+        }
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Vec2, Vec3};
+
+    fn vertex(x: f64, y: f64, depth: f64) -> Vertex {
+        Vertex {
+            position: Vec3::new(x, y, depth),
+            screen_position: Vec2::new(x, y),
+            normal: Vec3::new(0.0, 0.0, -1.0),
+            w: 1.0,
+        }
+    }
+
+    #[test]
+    fn nearest_triangle_owns_pixel() {
+        let mut ids = IdBuffer::new(8, 8);
+        let far = [
+            vertex(0.0, 0.0, 5.0),
+            vertex(0.0, 8.0, 5.0),
+            vertex(8.0, 0.0, 5.0),
+        ];
+        let near = [
+            vertex(0.0, 0.0, 3.0),
+            vertex(0.0, 4.0, 3.0),
+            vertex(4.0, 0.0, 3.0),
+        ];
+        // Submission order must not matter
+        ids.draw_triangle(&near[0], &near[1], &near[2], 7);
+        ids.draw_triangle(&far[0], &far[1], &far[2], 2);
+        assert_eq!(ids.get(1, 1), Some((7, 3.0)));
+        assert_eq!(ids.get(5, 1), Some((2, 5.0)));
+        assert_eq!(ids.get(7, 7), None);
+        assert_eq!(ids.get(8, 0), None);
+    }
+
+    #[test]
+    fn outlines_follow_visible_face_borders() {
+        let mut ids = IdBuffer::new(12, 12);
+        let back = [
+            vertex(0.0, 0.0, 5.0),
+            vertex(0.0, 12.0, 5.0),
+            vertex(12.0, 12.0, 5.0),
+            vertex(12.0, 0.0, 5.0),
+        ];
+        let front = [
+            vertex(4.0, 4.0, 3.0),
+            vertex(4.0, 8.0, 3.0),
+            vertex(8.0, 8.0, 3.0),
+            vertex(8.0, 4.0, 3.0),
+        ];
+        // Two triangles per square, so face 0 has an inner diagonal edge
+        for (square, id) in [(&back, 0), (&front, 1)] {
+            ids.draw_triangle(&square[0], &square[1], &square[2], id);
+            ids.draw_triangle(&square[0], &square[2], &square[3], id);
+        }
+        let mask = ids.outline(&[0], 0, |_, _, face, _| Some(face as usize));
+        let marked = |x: usize, y: usize| mask[y * 12 + x];
+        // The window edge and the rim around the front face are borders of face 0
+        assert!(marked(0, 6) && marked(11, 6));
+        assert!(marked(3, 6) && !marked(4, 6));
+        // Nothing on the diagonal inside face 0 or behind face 1
+        assert!(!marked(6, 2) && !marked(2, 9));
+        assert!(!marked(6, 6));
+
+        let thick = ids.outline(&[0], 1, |_, _, face, _| Some(face as usize));
+        assert!(thick[6 * 12 + 4] && !thick[6 * 12 + 5]);
+        assert!(ids.outline(&[], 1, |_, _, _, _| None).iter().all(|&m| !m));
+    }
+}
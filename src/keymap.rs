@@ -0,0 +1,817 @@
+use crate::postprocess::PostEffect;
+use crate::views::{StandardView, BOOKMARK_SLOTS};
+use druid::keyboard_types::{Code, Key, KeyState};
+use druid::KeyEvent;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Name of the key bindings file inside the configuration directory
+const KEYMAP_FILE: &str = "keys.toml";
+
+/// Something the user can trigger from the keyboard
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    ShowHelp,
+    Quit,
+    ToggleDebug,
+    TogglePause,
+    ToggleWireframe,
+    SpeedUp,
+    SlowDown,
+    /// Adds to or subtracts from the auto-rotation around one axis (0 to 2)
+    IncreaseSpin(usize),
+    DecreaseSpin(usize),
+    CycleRotationMode,
+    CycleSelectionMode,
+    ToggleIds,
+    ToggleLight,
+    TogglePanel,
+    CycleGizmo,
+    /// Held while dragging to constrain the gizmo to a world axis (0 to 2)
+    ConstrainAxis(usize),
+    FrameAll,
+    FrameSelection,
+    ToggleCameraMode,
+    /// Held to move the fly camera
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    /// Held to rotate the view, or to look around with the fly camera
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    /// Held to pan the camera
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    ToggleProjection,
+    CycleTransparency,
+    CycleToneMapping,
+    CyclePalette,
+    DecreaseExposure,
+    IncreaseExposure,
+    ToggleSsao,
+    DecreaseSsaoRadius,
+    IncreaseSsaoRadius,
+    DecreaseSsaoStrength,
+    IncreaseSsaoStrength,
+    CycleBackground,
+    CycleFog,
+    TogglePostEffect(PostEffect),
+    MovePostEffectEarlier(PostEffect),
+    View(StandardView),
+    SaveBookmark(u8),
+    RestoreBookmark(u8),
+    Undo,
+    Redo,
+    Reset,
+}
+
+impl Action {
+    /// Every action, in the order they are listed in the help window
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::ShowHelp,
+            Action::Quit,
+            Action::ToggleDebug,
+            Action::TogglePause,
+            Action::ToggleWireframe,
+            Action::SpeedUp,
+            Action::SlowDown,
+            Action::IncreaseSpin(0),
+            Action::IncreaseSpin(1),
+            Action::IncreaseSpin(2),
+            Action::DecreaseSpin(0),
+            Action::DecreaseSpin(1),
+            Action::DecreaseSpin(2),
+            Action::CycleRotationMode,
+            Action::CycleSelectionMode,
+            Action::ToggleIds,
+            Action::ToggleLight,
+            Action::TogglePanel,
+            Action::CycleGizmo,
+            Action::ConstrainAxis(0),
+            Action::ConstrainAxis(1),
+            Action::ConstrainAxis(2),
+            Action::FrameAll,
+            Action::FrameSelection,
+            Action::ToggleCameraMode,
+            Action::MoveForward,
+            Action::MoveLeft,
+            Action::MoveBack,
+            Action::MoveRight,
+            Action::MoveUp,
+            Action::MoveDown,
+            Action::OrbitLeft,
+            Action::OrbitRight,
+            Action::OrbitUp,
+            Action::OrbitDown,
+            Action::PanLeft,
+            Action::PanRight,
+            Action::PanUp,
+            Action::PanDown,
+            Action::ZoomIn,
+            Action::ZoomOut,
+            Action::ToggleProjection,
+            Action::CycleTransparency,
+            Action::CycleToneMapping,
+            Action::CyclePalette,
+            Action::DecreaseExposure,
+            Action::IncreaseExposure,
+            Action::ToggleSsao,
+            Action::DecreaseSsaoRadius,
+            Action::IncreaseSsaoRadius,
+            Action::DecreaseSsaoStrength,
+            Action::IncreaseSsaoStrength,
+            Action::CycleBackground,
+            Action::CycleFog,
+        ];
+        actions.extend(PostEffect::ALL.map(Action::TogglePostEffect));
+        actions.extend(PostEffect::ALL.map(Action::MovePostEffectEarlier));
+        actions.extend(StandardView::ALL.map(Action::View));
+        actions.extend((1..=BOOKMARK_SLOTS).map(Action::SaveBookmark));
+        actions.extend((1..=BOOKMARK_SLOTS).map(Action::RestoreBookmark));
+        actions.extend([Action::Undo, Action::Redo, Action::Reset]);
+        actions
+    }
+
+    /// Name of the action in the key bindings file
+    pub fn name(self) -> String {
+        let snake_case = |name: &str| name.to_lowercase().replace(' ', "_");
+        match self {
+            Action::ShowHelp => "show_help".into(),
+            Action::Quit => "quit".into(),
+            Action::ToggleDebug => "toggle_debug".into(),
+            Action::TogglePause => "toggle_pause".into(),
+            Action::ToggleWireframe => "toggle_wireframe".into(),
+            Action::SpeedUp => "speed_up".into(),
+            Action::SlowDown => "slow_down".into(),
+            Action::IncreaseSpin(axis) => format!("increase_spin_{}", ["x", "y", "z"][axis]),
+            Action::DecreaseSpin(axis) => format!("decrease_spin_{}", ["x", "y", "z"][axis]),
+            Action::CycleRotationMode => "cycle_rotation_mode".into(),
+            Action::CycleSelectionMode => "cycle_selection_mode".into(),
+            Action::ToggleIds => "toggle_ids".into(),
+            Action::ToggleLight => "toggle_light".into(),
+            Action::TogglePanel => "toggle_panel".into(),
+            Action::CycleGizmo => "cycle_gizmo".into(),
+            Action::ConstrainAxis(axis) => format!("constrain_{}", ["x", "y", "z"][axis]),
+            Action::FrameAll => "frame_all".into(),
+            Action::FrameSelection => "frame_selection".into(),
+            Action::ToggleCameraMode => "toggle_camera_mode".into(),
+            Action::MoveForward => "move_forward".into(),
+            Action::MoveBack => "move_back".into(),
+            Action::MoveLeft => "move_left".into(),
+            Action::MoveRight => "move_right".into(),
+            Action::MoveUp => "move_up".into(),
+            Action::MoveDown => "move_down".into(),
+            Action::OrbitLeft => "orbit_left".into(),
+            Action::OrbitRight => "orbit_right".into(),
+            Action::OrbitUp => "orbit_up".into(),
+            Action::OrbitDown => "orbit_down".into(),
+            Action::PanLeft => "pan_left".into(),
+            Action::PanRight => "pan_right".into(),
+            Action::PanUp => "pan_up".into(),
+            Action::PanDown => "pan_down".into(),
+            Action::ZoomIn => "zoom_in".into(),
+            Action::ZoomOut => "zoom_out".into(),
+            Action::ToggleProjection => "toggle_projection".into(),
+            Action::CycleTransparency => "cycle_transparency".into(),
+            Action::CycleToneMapping => "cycle_tone_mapping".into(),
+            Action::CyclePalette => "cycle_palette".into(),
+            Action::DecreaseExposure => "decrease_exposure".into(),
+            Action::IncreaseExposure => "increase_exposure".into(),
+            Action::ToggleSsao => "toggle_ssao".into(),
+            Action::DecreaseSsaoRadius => "decrease_ssao_radius".into(),
+            Action::IncreaseSsaoRadius => "increase_ssao_radius".into(),
+            Action::DecreaseSsaoStrength => "decrease_ssao_strength".into(),
+            Action::IncreaseSsaoStrength => "increase_ssao_strength".into(),
+            Action::CycleBackground => "cycle_background".into(),
+            Action::CycleFog => "cycle_fog".into(),
+            Action::TogglePostEffect(effect) => format!("toggle_{}", snake_case(effect.name())),
+            Action::MovePostEffectEarlier(effect) => {
+                format!("move_{}_earlier", snake_case(effect.name()))
+            }
+            Action::View(view) => format!("view_{}", snake_case(view.name())),
+            Action::SaveBookmark(slot) => format!("save_bookmark_{}", slot),
+            Action::RestoreBookmark(slot) => format!("restore_bookmark_{}", slot),
+            Action::Undo => "undo".into(),
+            Action::Redo => "redo".into(),
+            Action::Reset => "reset".into(),
+        }
+    }
+
+    /// Description shown in the help window; consecutive actions sharing one are listed together
+    pub fn description(self) -> String {
+        match self {
+            Action::ShowHelp => "Open this help window".into(),
+            Action::Quit => "Quit the application".into(),
+            Action::ToggleDebug => "Toggle debug mode".into(),
+            Action::TogglePause => "Pause/unpause rotation".into(),
+            Action::ToggleWireframe => "Toggle wireframe mode".into(),
+            Action::SpeedUp => "Speed up the rotation".into(),
+            Action::SlowDown => "Slow down the rotation".into(),
+            Action::IncreaseSpin(_) => "Spin faster around an axis".into(),
+            Action::DecreaseSpin(_) => "Spin slower around an axis, reversing past zero".into(),
+            Action::CycleRotationMode => {
+                "Cycle drag rotation (arcball, turntable, screen axes)".into()
+            }
+            Action::CycleSelectionMode => "Cycle selection (face, edge, vertex)".into(),
+            Action::ToggleIds => "Show the face ID buffer".into(),
+            Action::ToggleLight => "Show/hide the light gizmo".into(),
+            Action::TogglePanel => "Show/hide the settings panel".into(),
+            Action::CycleGizmo => "Cycle gizmo (off, translate, rotate, scale)".into(),
+            Action::ConstrainAxis(_) => {
+                "Hold while dragging to constrain the gizmo to an axis".into()
+            }
+            Action::FrameAll => "Frame the whole cube".into(),
+            Action::FrameSelection => "Frame the selection".into(),
+            Action::ToggleCameraMode => "Toggle orbit/fly camera".into(),
+            Action::MoveForward
+            | Action::MoveBack
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::MoveUp
+            | Action::MoveDown => "Move the fly camera".into(),
+            Action::OrbitLeft | Action::OrbitRight | Action::OrbitUp | Action::OrbitDown => {
+                "Rotate the view (fly camera: look around)".into()
+            }
+            Action::PanLeft | Action::PanRight | Action::PanUp | Action::PanDown => {
+                "Pan the camera".into()
+            }
+            Action::ZoomIn => "Zoom in (fly camera: speed up)".into(),
+            Action::ZoomOut => "Zoom out (fly camera: slow down)".into(),
+            Action::ToggleProjection => "Toggle perspective/orthographic projection".into(),
+            Action::CycleTransparency => "Cycle transparency (opaque, sorted, A-buffer)".into(),
+            Action::CycleToneMapping => {
+                "Cycle tone mapping (clamp, Reinhard, ACES, exposure)".into()
+            }
+            Action::CyclePalette => {
+                "Cycle face colors (default, pastel, grayscale, Okabe-Ito)".into()
+            }
+            Action::DecreaseExposure => "Decrease exposure".into(),
+            Action::IncreaseExposure => "Increase exposure".into(),
+            Action::ToggleSsao => "Toggle screen-space ambient occlusion".into(),
+            Action::DecreaseSsaoRadius => "Decrease the ambient occlusion radius".into(),
+            Action::IncreaseSsaoRadius => "Increase the ambient occlusion radius".into(),
+            Action::DecreaseSsaoStrength => "Decrease the ambient occlusion strength".into(),
+            Action::IncreaseSsaoStrength => "Increase the ambient occlusion strength".into(),
+            Action::CycleBackground => "Cycle background (solid, gradient, image)".into(),
+            Action::CycleFog => "Cycle depth fog (off, linear, exponential)".into(),
+            Action::TogglePostEffect(effect) => format!("Toggle {}", effect.name()),
+            Action::MovePostEffectEarlier(_) => {
+                "Move an effect earlier in the chain (the first one moves to the end)".into()
+            }
+            Action::View(view) => format!("{} view", view.name()),
+            Action::SaveBookmark(_) => "Save the view to a bookmark".into(),
+            Action::RestoreBookmark(_) => "Restore a bookmarked view".into(),
+            Action::Undo => "Undo".into(),
+            Action::Redo => "Redo".into(),
+            Action::Reset => "Reset cube and camera".into(),
+        }
+    }
+
+    /// Keys bound to the action unless the key bindings file says otherwise
+    fn default_keys(self) -> Vec<String> {
+        let keys: &[&str] = match self {
+            Action::ShowHelp => &["h"],
+            Action::Quit => &["q"],
+            // The second keys stay free while the fly camera moves with the letters
+            Action::ToggleDebug => &["d", "F12"],
+            Action::TogglePause => &["p"],
+            Action::ToggleWireframe => &["w", "F9"],
+            Action::SpeedUp => &["+", "="],
+            Action::SlowDown => &["-", "_"],
+            Action::IncreaseSpin(0) => &["Alt+x"],
+            Action::IncreaseSpin(1) => &["Alt+y"],
+            Action::IncreaseSpin(_) => &["Alt+z"],
+            Action::DecreaseSpin(0) => &["Alt+Shift+x"],
+            Action::DecreaseSpin(1) => &["Alt+Shift+y"],
+            Action::DecreaseSpin(_) => &["Alt+Shift+z"],
+            Action::CycleRotationMode => &["m"],
+            Action::CycleSelectionMode => &["n"],
+            Action::ToggleIds => &["i"],
+            Action::ToggleLight => &["l"],
+            Action::TogglePanel => &[","],
+            Action::CycleGizmo => &["Tab"],
+            Action::ConstrainAxis(0) => &["x"],
+            Action::ConstrainAxis(1) => &["y"],
+            Action::ConstrainAxis(_) => &["z"],
+            Action::FrameAll => &["f", "Home"],
+            Action::FrameSelection => &[".", "NumpadDecimal"],
+            Action::ToggleCameraMode => &["c"],
+            Action::MoveForward => &["w"],
+            Action::MoveBack => &["s"],
+            Action::MoveLeft => &["a"],
+            Action::MoveRight => &["d"],
+            Action::MoveUp => &["Space"],
+            Action::MoveDown => &["Shift"],
+            Action::OrbitLeft => &["ArrowLeft", "Numpad4"],
+            Action::OrbitRight => &["ArrowRight", "Numpad6"],
+            Action::OrbitUp => &["ArrowUp", "Numpad8"],
+            Action::OrbitDown => &["ArrowDown", "Numpad2"],
+            Action::PanLeft => &["Shift+ArrowLeft"],
+            Action::PanRight => &["Shift+ArrowRight"],
+            Action::PanUp => &["Shift+ArrowUp"],
+            Action::PanDown => &["Shift+ArrowDown"],
+            Action::ZoomIn => &["PageUp", "NumpadAdd"],
+            Action::ZoomOut => &["PageDown", "NumpadSubtract"],
+            Action::ToggleProjection => &["v", "Numpad5"],
+            Action::CycleTransparency => &["t"],
+            Action::CycleToneMapping => &["e"],
+            Action::CyclePalette => &["k"],
+            Action::DecreaseExposure => &["["],
+            Action::IncreaseExposure => &["]"],
+            Action::ToggleSsao => &["o"],
+            Action::DecreaseSsaoRadius => &["Alt+["],
+            Action::IncreaseSsaoRadius => &["Alt+]"],
+            Action::DecreaseSsaoStrength => &["Ctrl+["],
+            Action::IncreaseSsaoStrength => &["Ctrl+]"],
+            Action::CycleBackground => &["b"],
+            Action::CycleFog => &["g"],
+            Action::Undo => &["Ctrl+z"],
+            Action::Redo => &["Ctrl+Shift+z"],
+            Action::Reset => &["r"],
+            Action::TogglePostEffect(effect) => {
+                let index = PostEffect::ALL.iter().position(|&e| e == effect).unwrap();
+                return vec![(index + 1).to_string()];
+            }
+            Action::MovePostEffectEarlier(effect) => {
+                let index = PostEffect::ALL.iter().position(|&e| e == effect).unwrap();
+                return vec![format!("Alt+{}", index + 1)];
+            }
+            Action::View(view) => {
+                let index = StandardView::ALL.iter().position(|&v| v == view).unwrap();
+                // The keypad follows the layout common in 3D modelling programs
+                let numpad = match view {
+                    StandardView::Front => "Numpad1",
+                    StandardView::Back => "Ctrl+Numpad1",
+                    StandardView::Top => "Numpad7",
+                    StandardView::Bottom => "Ctrl+Numpad7",
+                    StandardView::Left => "Ctrl+Numpad3",
+                    StandardView::Right => "Numpad3",
+                    StandardView::Isometric => "Numpad9",
+                };
+                return vec![format!("F{}", index + 1), numpad.to_string()];
+            }
+            Action::SaveBookmark(slot) => return vec![format!("Ctrl+{}", slot)],
+            Action::RestoreBookmark(slot) => return vec![format!("Shift+{}", slot)],
+        };
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    /// Whether the action lasts while its key is held rather than firing on a press
+    pub fn is_held(self) -> bool {
+        matches!(
+            self,
+            Action::ConstrainAxis(_)
+                | Action::MoveForward
+                | Action::MoveBack
+                | Action::MoveLeft
+                | Action::MoveRight
+                | Action::MoveUp
+                | Action::MoveDown
+                | Action::OrbitLeft
+                | Action::OrbitRight
+                | Action::OrbitUp
+                | Action::OrbitDown
+                | Action::PanLeft
+                | Action::PanRight
+                | Action::PanUp
+                | Action::PanDown
+        )
+    }
+
+    /// Whether the action can be used while the program is paused.
+    ///
+    /// Views and bookmarks are included, since a still cube is the easiest to inspect.
+    pub fn works_while_paused(self) -> bool {
+        matches!(
+            self,
+            Action::ShowHelp
+                | Action::Quit
+                | Action::ToggleDebug
+                | Action::TogglePause
+                | Action::View(_)
+                | Action::SaveBookmark(_)
+                | Action::RestoreBookmark(_)
+        )
+    }
+}
+
+/// Key that a chord is triggered by
+#[derive(Clone, Debug, PartialEq)]
+enum ChordKey {
+    /// Character produced by the key, in lowercase
+    Character(String),
+    /// Non-character key such as `F1`, `Tab` or `ArrowUp`
+    Named(Key),
+    /// Physical key position such as `NumpadAdd`, for keys that produce the same character
+    Physical(Code),
+}
+
+/// A key together with the modifiers that must be held with it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chord {
+    key: ChordKey,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl Chord {
+    /// Whether the key of `event` is the chord's key, whatever the modifiers
+    fn key_matches(&self, event: &KeyEvent) -> bool {
+        match &self.key {
+            ChordKey::Character(c) => match &event.key {
+                // Letters and digits are compared as typed so that bindings follow the layout
+                Key::Character(s) if s.chars().all(char::is_alphanumeric) => s.to_lowercase() == *c,
+                // Modifiers can turn digits into symbols or letters into control
+                // characters, so fall back to the key's position on a US layout
+                Key::Character(s) if s.to_lowercase() == *c => true,
+                _ => us_layout_code(c) == Some(event.code),
+            },
+            ChordKey::Named(key) => event.key == *key,
+            ChordKey::Physical(code) => event.code == *code,
+        }
+    }
+
+    /// How closely the chord matches a key press, or `None` if it does not match.
+    ///
+    /// Exact modifiers rank physical keys above named keys above characters, as the
+    /// numeric keypad produces both. Chords without Shift also match with Shift held,
+    /// at the lowest rank, so that `+` works where it takes Shift to type.
+    fn specificity(&self, event: &KeyEvent) -> Option<u8> {
+        let mods = event.mods;
+        if !self.key_matches(event) || self.ctrl != mods.ctrl() || self.alt != mods.alt() {
+            return None;
+        }
+        if self.shift == mods.shift() {
+            Some(match self.key {
+                ChordKey::Physical(_) => 3,
+                ChordKey::Named(_) => 2,
+                ChordKey::Character(_) => 1,
+            })
+        } else if !self.shift {
+            Some(0)
+        } else {
+            None
+        }
+    }
+}
+
+/// Physical code of a letter or digit key on a US layout
+fn us_layout_code(c: &str) -> Option<Code> {
+    let mut chars = c.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    let name = if c.is_ascii_digit() {
+        format!("Digit{}", c)
+    } else if c.is_ascii_alphabetic() {
+        format!("Key{}", c.to_ascii_uppercase())
+    } else {
+        return None;
+    };
+    Code::from_str(&name).ok()
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    /// Parses chords such as `r`, `F1`, `Ctrl+Shift+z`, `Ctrl++` or `NumpadAdd`
+    fn from_str(s: &str) -> Result<Chord, String> {
+        // The last part is the key, which may itself be a plus sign
+        let (modifiers, key) = match s.strip_suffix('+') {
+            Some(rest) if rest.is_empty() || rest.ends_with('+') => (rest, "+"),
+            _ => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        let mut chord = Chord {
+            key: ChordKey::Character(String::new()),
+            ctrl: false,
+            alt: false,
+            shift: false,
+        };
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "alt" => chord.alt = true,
+                "shift" => chord.shift = true,
+                _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, s)),
+            }
+        }
+        chord.key = if key.is_empty() {
+            return Err(format!("missing key in '{}'", s));
+        } else if key.eq_ignore_ascii_case("space") {
+            ChordKey::Character(" ".into())
+        } else if key.chars().count() == 1 {
+            ChordKey::Character(key.to_lowercase())
+        } else if let Ok(named) = Key::from_str(key) {
+            ChordKey::Named(named)
+        } else if let Ok(code) = Code::from_str(key) {
+            ChordKey::Physical(code)
+        } else {
+            return Err(format!("unknown key '{}' in '{}'", key, s));
+        };
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        match &self.key {
+            ChordKey::Character(c) if c == " " => write!(f, "Space"),
+            ChordKey::Character(c) => write!(f, "{}", c.to_uppercase()),
+            ChordKey::Named(key) => write!(f, "{}", key),
+            ChordKey::Physical(code) => write!(f, "{}", code),
+        }
+    }
+}
+
+/// Key bindings of every action.
+///
+/// The bindings file maps action names to lists of chords, replacing the default
+/// keys of the actions it lists; an empty list unbinds an action:
+///
+/// ```toml
+/// toggle_wireframe = ["w", "Ctrl+w"]
+/// view_front = ["F1", "Numpad1"]
+/// ```
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<Chord>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::all()
+            .into_iter()
+            .map(|action| {
+                let chords = action
+                    .default_keys()
+                    .iter()
+                    .map(|key| key.parse().expect("default key bindings are valid"))
+                    .collect();
+                (action, chords)
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// Location of the key bindings file in the platform configuration directory
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(KEYMAP_FILE))
+    }
+
+    /// Loads the key bindings file, or the default bindings if there is none
+    pub fn load() -> Result<Keymap, Box<dyn Error>> {
+        let mut keymap = Keymap::default();
+        if let Some(path) = Keymap::path().filter(|path| path.exists()) {
+            keymap.rebind(&fs::read_to_string(path)?)?;
+        }
+        Ok(keymap)
+    }
+
+    /// Replaces the bindings of the actions listed in a key bindings file
+    fn rebind(&mut self, contents: &str) -> Result<(), Box<dyn Error>> {
+        let entries: BTreeMap<String, Vec<String>> = toml::from_str(contents)?;
+        for (name, keys) in entries {
+            let (_, chords) = self
+                .bindings
+                .iter_mut()
+                .find(|(action, _)| action.name() == name)
+                .ok_or_else(|| format!("unknown action '{}'", name))?;
+            *chords = keys
+                .iter()
+                .map(|key| key.parse())
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(())
+    }
+
+    /// Held or pressed actions whose chords match a key press best
+    fn best_matches(&self, event: &KeyEvent, held: bool) -> Vec<Action> {
+        let ranked: Vec<(Action, u8)> = self
+            .bindings
+            .iter()
+            .filter(|(action, _)| action.is_held() == held)
+            .filter_map(|(action, chords)| {
+                let rank = chords
+                    .iter()
+                    .filter_map(|chord| chord.specificity(event))
+                    .max()?;
+                Some((*action, rank))
+            })
+            .collect();
+        let best = ranked.iter().map(|&(_, rank)| rank).max();
+        ranked
+            .into_iter()
+            .filter(|&(_, rank)| Some(rank) == best)
+            .map(|(action, _)| action)
+            .collect()
+    }
+
+    /// Action a key press triggers, if any, preferring the most specific binding
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.best_matches(event, false).first().copied()
+    }
+
+    /// Held actions bound to the key of `event`.
+    ///
+    /// Releases match whatever the modifiers, since they may be released first.
+    pub fn held_actions(&self, event: &KeyEvent) -> Vec<Action> {
+        if event.state == KeyState::Up {
+            return self
+                .bindings
+                .iter()
+                .filter(|(action, chords)| {
+                    action.is_held() && chords.iter().any(|chord| chord.key_matches(event))
+                })
+                .map(|(action, _)| *action)
+                .collect();
+        }
+        self.best_matches(event, true)
+    }
+
+    /// Help window lines listing the keys of every bound action
+    pub fn help(&self) -> Vec<String> {
+        let mut lines: Vec<(String, Vec<String>)> = Vec::new();
+        for (action, chords) in &self.bindings {
+            if chords.is_empty() {
+                continue;
+            }
+            let description = action.description();
+            let keys = chords.iter().map(Chord::to_string);
+            match lines.last_mut() {
+                Some((last, last_keys)) if *last == description => last_keys.extend(keys),
+                _ => lines.push((description, keys.collect())),
+            }
+        }
+        lines
+            .into_iter()
+            .map(|(description, keys)| format!(" - {}: {}", keys.join(", "), description))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use druid::Modifiers;
+
+    fn press(mods: Modifiers, key: &str, code: Code) -> KeyEvent {
+        let mut event = KeyEvent::for_test(mods, key);
+        event.code = code;
+        event
+    }
+
+    #[test]
+    fn chords_parse_and_display() {
+        for (text, shown) in [
+            ("r", "R"),
+            ("Ctrl+Shift+z", "Ctrl+Shift+Z"),
+            ("ctrl++", "Ctrl++"),
+            ("F7", "F7"),
+            ("space", "Space"),
+            ("NumpadAdd", "NumpadAdd"),
+        ] {
+            let chord: Chord = text.parse().unwrap();
+            assert_eq!(chord.to_string(), shown);
+        }
+        assert!("Hyper+a".parse::<Chord>().is_err());
+        assert!("NoSuchKey".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn presses_trigger_the_most_specific_binding() {
+        let keymap = Keymap::default();
+        let none = Modifiers::empty();
+        let action = |mods, key, code| keymap.action(&press(mods, key, code));
+        assert_eq!(action(none, "w", Code::KeyW), Some(Action::ToggleWireframe));
+        // Shift is ignored for characters unless a binding asks for it
+        assert_eq!(
+            action(Modifiers::SHIFT, "W", Code::KeyW),
+            Some(Action::ToggleWireframe)
+        );
+        assert_eq!(
+            action(Modifiers::SHIFT, "!", Code::Digit1),
+            Some(Action::RestoreBookmark(1))
+        );
+        assert_eq!(
+            action(Modifiers::CONTROL, "z", Code::KeyZ),
+            Some(Action::Undo)
+        );
+        assert_eq!(
+            action(Modifiers::CONTROL | Modifiers::SHIFT, "Z", Code::KeyZ),
+            Some(Action::Redo)
+        );
+        assert_eq!(
+            keymap.action(&KeyEvent::for_test(none, Key::F3)),
+            Some(Action::View(StandardView::Top))
+        );
+        assert_eq!(action(Modifiers::ALT, "w", Code::KeyW), None);
+
+        // Held actions see through Shift, and releases through every modifier
+        let w = press(Modifiers::SHIFT, "W", Code::KeyW);
+        assert_eq!(keymap.held_actions(&w), [Action::MoveForward]);
+        let mut z = press(Modifiers::CONTROL, "z", Code::KeyZ);
+        assert!(keymap.held_actions(&z).is_empty());
+        z.state = KeyState::Up;
+        assert_eq!(keymap.held_actions(&z), [Action::ConstrainAxis(2)]);
+        // Unless a held action asks for Shift itself
+        let left = KeyEvent::for_test(Modifiers::SHIFT, Key::ArrowLeft);
+        assert_eq!(keymap.held_actions(&left), [Action::PanLeft]);
+    }
+
+    #[test]
+    fn keypad_codes_win_over_the_keys_they_produce() {
+        let keymap = Keymap::default();
+        let none = Modifiers::empty();
+        // With Num Lock on the keypad types digits and signs, with it off it moves the caret
+        assert_eq!(
+            keymap.action(&press(none, "1", Code::Numpad1)),
+            Some(Action::View(StandardView::Front))
+        );
+        assert_eq!(
+            keymap.action(&press(none, "1", Code::Digit1)),
+            Some(Action::TogglePostEffect(PostEffect::Bloom))
+        );
+        assert_eq!(
+            keymap.action(&press(none, "+", Code::NumpadAdd)),
+            Some(Action::ZoomIn)
+        );
+        assert_eq!(
+            keymap.action(&press(none, "+", Code::Equal)),
+            Some(Action::SpeedUp)
+        );
+        let mut home = KeyEvent::for_test(none, Key::Home);
+        assert_eq!(keymap.action(&home), Some(Action::FrameAll));
+        home.code = Code::Numpad7;
+        assert_eq!(keymap.action(&home), Some(Action::View(StandardView::Top)));
+    }
+
+    #[test]
+    fn bindings_file_replaces_listed_actions() {
+        let mut keymap = Keymap::default();
+        keymap
+            .rebind("toggle_wireframe = [\"Ctrl+w\"]\nquit = []\n")
+            .unwrap();
+        let none = Modifiers::empty();
+        assert_eq!(keymap.action(&press(none, "w", Code::KeyW)), None);
+        assert_eq!(
+            keymap.action(&press(Modifiers::CONTROL, "w", Code::KeyW)),
+            Some(Action::ToggleWireframe)
+        );
+        assert_eq!(keymap.action(&press(none, "q", Code::KeyQ)), None);
+        assert!(keymap
+            .help()
+            .contains(&" - Ctrl+W: Toggle wireframe mode".to_string()));
+        assert!(keymap.help().contains(
+            &" - X, Y, Z: Hold while dragging to constrain the gizmo to an axis".to_string()
+        ));
+
+        assert!(keymap.rebind("no_such_action = [\"a\"]").is_err());
+        assert!(keymap.rebind("quit = [\"Ctrl+\"]").is_err());
+    }
+
+    #[test]
+    fn presses_stay_reachable_with_the_fly_camera() {
+        let movement = [
+            Action::MoveForward,
+            Action::MoveBack,
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::MoveUp,
+            Action::MoveDown,
+        ];
+        let taken: Vec<String> = movement.iter().flat_map(|a| a.default_keys()).collect();
+        for action in Action::all().into_iter().filter(|a| !a.is_held()) {
+            let keys = action.default_keys();
+            assert!(
+                keys.is_empty() || keys.iter().any(|key| !taken.contains(key)),
+                "{} is only bound to movement keys",
+                action.name()
+            );
+        }
+    }
+}
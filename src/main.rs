@@ -2,25 +2,44 @@ mod state;
 mod widget;
 mod vertex;
 mod math;
+mod mesh;
+mod quaternion;
+mod rotation;
+mod camera;
 mod graphics;
+mod abuffer;
+mod color;
+mod postprocess;
+mod ssao;
+mod background;
+mod fog;
+mod settings;
+mod views;
+mod picking;
+mod selection;
+mod idbuffer;
+mod gizmo;
+mod history;
+mod keymap;
+mod panel;
 
 use druid::{AppLauncher, PlatformError, WindowDesc, LocalizedString};
 
 fn main() -> Result<(), PlatformError> {
-    let main_window = WindowDesc::new(widget::CubeWidget::new())
+    let keymap = keymap::Keymap::load().unwrap_or_else(|err| {
+        eprintln!("Failed to load key bindings: {}", err);
+        keymap::Keymap::default()
+    });
+    let main_window = WindowDesc::new(panel::with_panel(widget::CubeWidget::new(keymap)))
         .title(LocalizedString::new("3D Cube with Per-Pixel Lighting"))
         .window_size((400.0, 400.0));
 
-    let initial_state = state::AppState {
-        angle_x: 0.0,
-        angle_y: 0.0,
-        translation: [0.0, 0.0], // Initialize translation
-        debug: false,
-        paused: false,
-        wireframe: false,
-        zoom: 1.0, // Initialize zoom level
-        light_position: [2.0, 2.0, -5.0],
-    };
+    // Start from the defaults and restore the settings saved by the last session
+    let mut initial_state = state::AppState::default();
+    match settings::Settings::load() {
+        Ok(settings) => settings.apply(&mut initial_state),
+        Err(err) => eprintln!("Failed to load settings: {}", err),
+    }
 
     AppLauncher::with_window(main_window).launch(initial_state)?;
 
@@ -1,83 +1,616 @@
-use druid::Color;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign};
 
-/// Edge function used in rasterization
-pub fn edge_function(a: &[f64; 2], b: &[f64; 2], c: &[f64; 2]) -> f64 {
-    (c[0] - a[0]) * (b[1] - a[1]) - (c[1] - a[1]) * (b[0] - a[0])
+/// Implements component-wise arithmetic operators for a vector type
+macro_rules! impl_vector_ops {
+    ($vec:ident { $($field:ident),+ }) => {
+        impl Add for $vec {
+            type Output = $vec;
+            fn add(self, rhs: $vec) -> $vec {
+                $vec { $($field: self.$field + rhs.$field),+ }
+            }
+        }
+
+        impl Sub for $vec {
+            type Output = $vec;
+            fn sub(self, rhs: $vec) -> $vec {
+                $vec { $($field: self.$field - rhs.$field),+ }
+            }
+        }
+
+        /// Component-wise product
+        impl Mul for $vec {
+            type Output = $vec;
+            fn mul(self, rhs: $vec) -> $vec {
+                $vec { $($field: self.$field * rhs.$field),+ }
+            }
+        }
+
+        impl Mul<f64> for $vec {
+            type Output = $vec;
+            fn mul(self, rhs: f64) -> $vec {
+                $vec { $($field: self.$field * rhs),+ }
+            }
+        }
+
+        impl Mul<$vec> for f64 {
+            type Output = $vec;
+            fn mul(self, rhs: $vec) -> $vec {
+                rhs * self
+            }
+        }
+
+        impl Div<f64> for $vec {
+            type Output = $vec;
+            fn div(self, rhs: f64) -> $vec {
+                $vec { $($field: self.$field / rhs),+ }
+            }
+        }
+
+        impl Neg for $vec {
+            type Output = $vec;
+            fn neg(self) -> $vec {
+                $vec { $($field: -self.$field),+ }
+            }
+        }
+
+        impl AddAssign for $vec {
+            fn add_assign(&mut self, rhs: $vec) {
+                $(self.$field += rhs.$field;)+
+            }
+        }
+
+        impl SubAssign for $vec {
+            fn sub_assign(&mut self, rhs: $vec) {
+                $(self.$field -= rhs.$field;)+
+            }
+        }
+
+        impl MulAssign<f64> for $vec {
+            fn mul_assign(&mut self, rhs: f64) {
+                $(self.$field *= rhs;)+
+            }
+        }
+
+        impl DivAssign<f64> for $vec {
+            fn div_assign(&mut self, rhs: f64) {
+                $(self.$field /= rhs;)+
+            }
+        }
+
+        impl $vec {
+            /// Dot product
+            pub fn dot(self, rhs: $vec) -> f64 {
+                0.0 $(+ self.$field * rhs.$field)+
+            }
+
+            /// Squared Euclidean length
+            pub fn length_squared(self) -> f64 {
+                self.dot(self)
+            }
+
+            /// Euclidean length
+            pub fn length(self) -> f64 {
+                self.length_squared().sqrt()
+            }
+
+            /// Returns the vector scaled to unit length
+            pub fn normalize(self) -> $vec {
+                self / self.length()
+            }
+
+            /// Linear interpolation towards `other` by `t`
+            pub fn lerp(self, other: $vec, t: f64) -> $vec {
+                self + (other - self) * t
+            }
+        }
+    };
+}
+
+/// Two-dimensional vector
+#[derive(Clone, Copy, Debug, Default, PartialEq, Data)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl_vector_ops!(Vec2 { x, y });
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub const fn new(x: f64, y: f64) -> Self {
+        Vec2 { x, y }
+    }
+
+    /// Appends a z component
+    pub fn extend(self, z: f64) -> Vec3 {
+        Vec3::new(self.x, self.y, z)
+    }
+}
+
+/// Three-dimensional vector
+#[derive(Clone, Copy, Debug, Default, PartialEq, Data, Serialize, Deserialize)]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl_vector_ops!(Vec3 { x, y, z });
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+    pub const X: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+    pub const Y: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+    pub const Z: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    /// Cross product
+    pub fn cross(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
+    /// Extends the vector to homogeneous coordinates with the given `w`
+    pub fn extend(self, w: f64) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+
+    /// Drops the `z` component
+    pub fn truncate(self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+}
+
+/// Four-dimensional (homogeneous) vector
+#[derive(Clone, Copy, Debug, Default, PartialEq, Data)]
+pub struct Vec4 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl_vector_ops!(Vec4 { x, y, z, w });
+
+impl Vec4 {
+    pub const fn new(x: f64, y: f64, z: f64, w: f64) -> Self {
+        Vec4 { x, y, z, w }
+    }
+
+    /// Drops the `w` component
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    /// Divides by `w` to get back from homogeneous coordinates
+    pub fn project(self) -> Vec3 {
+        self.truncate() / self.w
+    }
+}
+
+/// Row-major 3x3 matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat3 {
+    pub rows: [[f64; 3]; 3],
+}
+
+impl Mat3 {
+    pub const fn from_rows(rows: [[f64; 3]; 3]) -> Self {
+        Mat3 { rows }
+    }
+
+    /// Non-uniform scale
+    pub fn scale(scale: Vec3) -> Self {
+        Mat3::from_rows([[scale.x, 0.0, 0.0], [0.0, scale.y, 0.0], [0.0, 0.0, scale.z]])
+    }
+
+    /// Returns the row at `index` as a vector
+    pub fn row(&self, index: usize) -> Vec3 {
+        let [x, y, z] = self.rows[index];
+        Vec3::new(x, y, z)
+    }
+
+    /// Swaps rows and columns
+    pub fn transpose(&self) -> Mat3 {
+        let m = &self.rows;
+        Mat3::from_rows([
+            [m[0][0], m[1][0], m[2][0]],
+            [m[0][1], m[1][1], m[2][1]],
+            [m[0][2], m[1][2], m[2][2]],
+        ])
+    }
+
+    /// Determinant, computed as the scalar triple product of the rows
+    pub fn determinant(&self) -> f64 {
+        self.row(0).dot(self.row(1).cross(self.row(2)))
+    }
+
+    /// Inverse matrix, or `None` if the matrix is singular.
+    ///
+    /// Uses the same scale-independent test as [`Mat4::inverse`].
+    #[allow(dead_code)]
+    pub fn inverse(&self) -> Option<Mat3> {
+        let (r0, r1, r2) = (self.row(0), self.row(1), self.row(2));
+        let det = self.determinant();
+        if det.abs() <= SINGULAR_TOLERANCE * r0.length() * r1.length() * r2.length() {
+            return None;
+        }
+        // The columns of the inverse are the cross products of the rows
+        let c0 = r1.cross(r2) / det;
+        let c1 = r2.cross(r0) / det;
+        let c2 = r0.cross(r1) / det;
+        Some(Mat3::from_rows([[c0.x, c1.x, c2.x], [c0.y, c1.y, c2.y], [c0.z, c1.z, c2.z]]))
+    }
 }
 
-/// Checks if a point is inside a triangle using the edge function
-pub fn point_in_triangle(p: [f64; 2], a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> bool {
-    let w0 = edge_function(&b, &c, &p);
-    let w1 = edge_function(&c, &a, &p);
-    let w2 = edge_function(&a, &b, &p);
+impl Mul for Mat3 {
+    type Output = Mat3;
+    fn mul(self, rhs: Mat3) -> Mat3 {
+        let mut result = [[0.0; 3]; 3];
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Mat3::from_rows(result)
+    }
+}
 
-    (w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0) || (w0 <= 0.0 && w1 <= 0.0 && w2 <= 0.0)
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.row(0).dot(rhs), self.row(1).dot(rhs), self.row(2).dot(rhs))
+    }
 }
 
-/// Multiplies a 3x3 matrix by a 3-dimensional vector
-pub fn multiply_matrix_vector(matrix: &[[f64; 3]; 3], vector: &[f64; 3]) -> [f64; 3] {
-    let mut result = [0.0; 3];
-    for i in 0..3 {
-        result[i] = (0..3).map(|j| matrix[i][j] * vector[j]).sum();
+impl Index<usize> for Mat3 {
+    type Output = [f64; 3];
+    fn index(&self, index: usize) -> &[f64; 3] {
+        &self.rows[index]
     }
-    result
 }
 
-/// Multiplies two 3x3 matrices
-pub fn multiply_matrices(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
-    let mut result = [[0.0; 3]; 3];
-    for i in 0..3 {
-        for j in 0..3 {
-            for k in 0..3 {
-                result[i][j] += a[i][k] * b[k][j];
+/// Largest determinant, relative to the product of the row lengths, of a matrix treated as singular
+const SINGULAR_TOLERANCE: f64 = 1e-12;
+
+/// Row-major 4x4 matrix for homogeneous transforms.
+///
+/// Transforms compose right to left: `a * b` applies `b` first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    pub rows: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        rows: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub const fn from_rows(rows: [[f64; 4]; 4]) -> Self {
+        Mat4 { rows }
+    }
+
+    /// Embeds a 3x3 linear transform
+    pub fn from_mat3(m: Mat3) -> Self {
+        let r = m.rows;
+        Mat4::from_rows([
+            [r[0][0], r[0][1], r[0][2], 0.0],
+            [r[1][0], r[1][1], r[1][2], 0.0],
+            [r[2][0], r[2][1], r[2][2], 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Perspective projection: x and y are divided by the depth, which becomes 1/depth.
+    ///
+    /// Invertible, so screen points can be lifted back to view space.
+    pub fn perspective() -> Self {
+        Mat4::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ])
+    }
+
+    /// Translation by `offset`
+    pub fn translation(offset: Vec3) -> Self {
+        Mat4::from_rows([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Non-uniform scale
+    pub fn scale(scale: Vec3) -> Self {
+        Mat4::from_mat3(Mat3::scale(scale))
+    }
+
+    /// View transform of a camera at `eye` looking at `target`.
+    ///
+    /// The result maps to a space with x right, y down and z towards `target`,
+    /// with `up` appearing upwards on screen.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let forward = (target - eye).normalize();
+        let right = forward.cross(up).normalize();
+        let down = forward.cross(right);
+        Mat4::from_rows([
+            [right.x, right.y, right.z, -right.dot(eye)],
+            [down.x, down.y, down.z, -down.dot(eye)],
+            [forward.x, forward.y, forward.z, -forward.dot(eye)],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Upper-left 3x3 linear part
+    pub fn to_mat3(self) -> Mat3 {
+        let r = &self.rows;
+        Mat3::from_rows([
+            [r[0][0], r[0][1], r[0][2]],
+            [r[1][0], r[1][1], r[1][2]],
+            [r[2][0], r[2][1], r[2][2]],
+        ])
+    }
+
+    /// Determinant of the 3x3 minor that excludes `row` and `col`
+    fn minor(&self, row: usize, col: usize) -> f64 {
+        let mut m = [[0.0; 3]; 3];
+        let rows = (0..4).filter(|&r| r != row);
+        for (mi, r) in rows.enumerate() {
+            let cols = (0..4).filter(|&c| c != col);
+            for (mj, c) in cols.enumerate() {
+                m[mi][mj] = self.rows[r][c];
+            }
+        }
+        Mat3::from_rows(m).determinant()
+    }
+
+    /// Signed cofactor of the element at `row`, `col`
+    fn cofactor(&self, row: usize, col: usize) -> f64 {
+        let sign = if (row + col).is_multiple_of(2) { 1.0 } else { -1.0 };
+        sign * self.minor(row, col)
+    }
+
+    /// Swaps rows and columns
+    pub fn transpose(&self) -> Mat4 {
+        let mut result = [[0.0; 4]; 4];
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.rows[j][i];
+            }
+        }
+        Mat4::from_rows(result)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        (0..4).map(|j| self.rows[0][j] * self.cofactor(0, j)).sum()
+    }
+
+    /// Inverse matrix, or `None` if the matrix is singular.
+    ///
+    /// The determinant is compared against the product of the row lengths, its
+    /// largest possible magnitude, so the test does not depend on the matrix's scale.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let det = self.determinant();
+        let bound: f64 = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|v| v * v).sum::<f64>().sqrt())
+            .product();
+        if det.abs() <= SINGULAR_TOLERANCE * bound {
+            return None;
+        }
+        // Inverse is the transposed cofactor matrix divided by the determinant
+        let mut cofactors = [[0.0; 4]; 4];
+        for (i, row) in cofactors.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.cofactor(i, j) / det;
             }
         }
+        Some(Mat4::from_rows(cofactors).transpose())
+    }
+
+    /// Transforms a point, including translation and the perspective divide
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        (*self * point.extend(1.0)).project()
+    }
+
+    /// Transforms a direction, ignoring translation
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        (*self * vector.extend(0.0)).truncate()
     }
-    result
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut result = [[0.0; 4]; 4];
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[i][k] * rhs.rows[k][j]).sum();
+            }
+        }
+        Mat4::from_rows(result)
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+    fn mul(self, rhs: Vec4) -> Vec4 {
+        let row = |i: usize| {
+            let [x, y, z, w] = self.rows[i];
+            Vec4::new(x, y, z, w).dot(rhs)
+        };
+        Vec4::new(row(0), row(1), row(2), row(3))
+    }
+}
+
+impl Index<usize> for Mat4 {
+    type Output = [f64; 4];
+    fn index(&self, index: usize) -> &[f64; 4] {
+        &self.rows[index]
+    }
+}
+
+/// Edge function used in rasterization
+pub fn edge_function(a: Vec2, b: Vec2, c: Vec2) -> f64 {
+    (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
 }
 
 /// Calculates the normal vector of a triangle
-pub fn calculate_normal(a: &[f64; 3], b: &[f64; 3], c: &[f64; 3]) -> [f64; 3] {
-    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
-    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
-    let normal = [
-        u[1] * v[2] - u[2] * v[1],
-        u[2] * v[0] - u[0] * v[2],
-        u[0] * v[1] - u[1] * v[0],
-    ];
-    let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
-    [normal[0] / length, normal[1] / length, normal[2] / length]
+pub fn calculate_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    (b - a).cross(c - a).normalize()
 }
 
+/// Intensity of the ambient light term
+pub const AMBIENT_INTENSITY: f64 = 0.1;
+
 /// Calculates the light intensity based on the normal vector and light position
+///
+/// The result is the ambient term plus the diffuse term scaled by the light's
+/// `brightness`, so it never drops below [`AMBIENT_INTENSITY`].
 pub fn calculate_light_intensity(
-    normal: &[f64; 3],
-    position: &[f64; 3],
-    light_pos: &[f64; 3],
+    normal: Vec3,
+    position: Vec3,
+    light_pos: Vec3,
+    brightness: f64,
 ) -> f64 {
-    let light_dir = [
-        light_pos[0] - position[0],
-        light_pos[1] - position[1],
-        light_pos[2] - position[2],
-    ];
-    let length = (light_dir[0] * light_dir[0]
-        + light_dir[1] * light_dir[1]
-        + light_dir[2] * light_dir[2])
-        .sqrt();
-    let light_dir = [
-        light_dir[0] / length,
-        light_dir[1] / length,
-        light_dir[2] / length,
-    ];
-    let dot_product =
-        normal[0] * light_dir[0] + normal[1] * light_dir[1] + normal[2] * light_dir[2];
-    dot_product.max(0.1) // Ensure a minimum ambient light
-}
-
-/// Applies lighting to a color
-pub fn apply_lighting(color: Color, intensity: f64) -> Color {
-    let r = (color.as_rgba8().0 as f64 * intensity).min(255.0) as u8;
-    let g = (color.as_rgba8().1 as f64 * intensity).min(255.0) as u8;
-    let b = (color.as_rgba8().2 as f64 * intensity).min(255.0) as u8;
-    Color::rgb8(r, g, b)
+    AMBIENT_INTENSITY + calculate_diffuse_intensity(normal, position, light_pos) * brightness
+}
+
+/// Calculates the diffuse (Lambertian) part of the light intensity
+pub fn calculate_diffuse_intensity(normal: Vec3, position: Vec3, light_pos: Vec3) -> f64 {
+    let light_dir = (light_pos - position).normalize();
+    normal.dot(light_dir).max(0.0) * (1.0 - AMBIENT_INTENSITY)
+}
+
+/// Applies lighting to a linear RGBA color, leaving alpha untouched.
+///
+/// The result is unclamped HDR radiance; tone mapping happens when the frame is encoded.
+pub fn apply_lighting(color: [f64; 4], intensity: f64) -> [f64; 4] {
+    [
+        color[0] * intensity,
+        color[1] * intensity,
+        color[2] * intensity,
+        color[3],
+    ]
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Rotation around the X-axis by `angle` radians
+    pub(crate) fn rotation_x(angle: f64) -> Mat3 {
+        let (sin, cos) = angle.sin_cos();
+        Mat3::from_rows([[1.0, 0.0, 0.0], [0.0, cos, -sin], [0.0, sin, cos]])
+    }
+
+    /// Rotation around the Y-axis by `angle` radians
+    pub(crate) fn rotation_y(angle: f64) -> Mat3 {
+        let (sin, cos) = angle.sin_cos();
+        Mat3::from_rows([[cos, 0.0, sin], [0.0, 1.0, 0.0], [-sin, 0.0, cos]])
+    }
+
+    /// Rotation around the Z-axis by `angle` radians
+    pub(crate) fn rotation_z(angle: f64) -> Mat3 {
+        let (sin, cos) = angle.sin_cos();
+        Mat3::from_rows([[cos, -sin, 0.0], [sin, cos, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    fn assert_mat4_eq(a: Mat4, b: Mat4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a[i][j] - b[i][j]).abs() < 1e-9, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn cross_follows_right_hand_rule() {
+        assert_eq!(Vec3::X.cross(Vec3::Y), Vec3::Z);
+        assert_eq!(Vec3::Y.cross(Vec3::Z), Vec3::X);
+    }
+
+    #[test]
+    fn transforms_compose_right_to_left() {
+        let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0)) * Mat4::scale(Vec3::new(2.0, 2.0, 2.0));
+        assert_eq!(m.transform_point(Vec3::new(1.0, 1.0, 1.0)), Vec3::new(3.0, 4.0, 5.0));
+        // Directions ignore the translation
+        assert_eq!(m.transform_vector(Vec3::X), Vec3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let m = Mat4::translation(Vec3::new(1.0, -2.0, 0.5))
+            * Mat4::from_mat3(rotation_y(0.7) * rotation_x(-0.3))
+            * Mat4::scale(Vec3::new(2.0, 3.0, 4.0));
+        assert_mat4_eq(m * m.inverse().unwrap(), Mat4::IDENTITY);
+        assert!((m.determinant() - 24.0).abs() < 1e-9);
+        assert!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn rotation_inverse_is_transpose() {
+        let r = rotation_z(0.4) * rotation_y(-1.2) * rotation_x(2.5);
+        let inverse = r.inverse().unwrap();
+        let transpose = r.transpose();
+        for i in 0..3 {
+            assert!((inverse.row(i) - transpose.row(i)).length() < 1e-9);
+        }
+        let m = Mat3::scale(Vec3::new(2.0, 4.0, 0.5)) * r;
+        let v = Vec3::new(1.0, -2.0, 3.0);
+        assert!((m.inverse().unwrap() * (m * v) - v).length() < 1e-9);
+        assert!(Mat3::scale(Vec3::new(1e6, 1e6, 0.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn transpose_swaps_rows_and_columns() {
+        let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0));
+        let t = m.transpose();
+        assert_eq!(t[3], [1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(t[0][3], 0.0);
+        assert_mat4_eq(t.transpose(), m);
+    }
+
+    #[test]
+    fn singularity_does_not_depend_on_scale() {
+        // Tiny but well-conditioned
+        let small = Mat4::scale(Vec3::new(1e-6, 1e-6, 1e-6));
+        assert_mat4_eq(small * small.inverse().unwrap(), Mat4::IDENTITY);
+        // Large determinant, but the last row is almost the sum of the first two
+        let flat = Mat4::from_rows([
+            [1e6, 0.0, 0.0, 0.0],
+            [0.0, 1e6, 0.0, 0.0],
+            [1e6, 1e6, 1e-9, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert!(flat.determinant() > f64::EPSILON);
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn perspective_round_trips_through_its_inverse() {
+        let p = Mat4::perspective();
+        let point = Vec3::new(1.0, -2.0, 4.0);
+        assert_eq!(p.transform_point(point), Vec3::new(0.25, -0.5, 0.25));
+        let back = p.inverse().unwrap().transform_point(p.transform_point(point));
+        assert!((back - point).length() < 1e-12);
+    }
 }
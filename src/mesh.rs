@@ -0,0 +1,169 @@
+use crate::math::Vec3;
+
+/// Polygon mesh with faces wound clockwise as seen from outside in view space
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    /// Vertex indices of each (convex) polygon
+    pub faces: Vec<Vec<usize>>,
+}
+
+impl Mesh {
+    /// Cube spanning -1.0 to 1.0 on every axis
+    pub fn cube() -> Mesh {
+        Mesh {
+            positions: vec![
+                Vec3::new(-1.0, -1.0, -1.0), // 0
+                Vec3::new(1.0, -1.0, -1.0),  // 1
+                Vec3::new(1.0, 1.0, -1.0),   // 2
+                Vec3::new(-1.0, 1.0, -1.0),  // 3
+                Vec3::new(-1.0, -1.0, 1.0),  // 4
+                Vec3::new(1.0, -1.0, 1.0),   // 5
+                Vec3::new(1.0, 1.0, 1.0),    // 6
+                Vec3::new(-1.0, 1.0, 1.0),   // 7
+            ],
+            faces: vec![
+                vec![0, 1, 2, 3],
+                vec![5, 4, 7, 6],
+                vec![4, 0, 3, 7],
+                vec![1, 5, 6, 2],
+                vec![4, 5, 1, 0],
+                vec![3, 2, 6, 7],
+            ],
+        }
+    }
+
+    /// Unique edges shared by the faces, as pairs of vertex indices
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = Vec::new();
+        for face in &self.faces {
+            for (i, &a) in face.iter().enumerate() {
+                let b = face[(i + 1) % face.len()];
+                let edge = (a.min(b), a.max(b));
+                if !edges.contains(&edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+        edges
+    }
+
+    /// Outward unit normal of a face whose vertices have been moved to `positions`
+    pub fn face_normal(&self, positions: &[Vec3], face: usize) -> Vec3 {
+        let indices = &self.faces[face];
+        let [a, b, c] = [indices[0], indices[1], indices[2]].map(|index| positions[index]);
+        // Clockwise winding seen from outside makes the right-handed normal point inwards
+        (c - a).cross(b - a).normalize()
+    }
+
+    /// Splits every face into a fan of triangles, tagged with the index of their face
+    pub fn triangles(&self) -> Vec<(usize, [usize; 3])> {
+        let mut triangles = Vec::new();
+        for (face_index, face) in self.faces.iter().enumerate() {
+            for i in 1..face.len().saturating_sub(1) {
+                triangles.push((face_index, [face[0], face[i], face[i + 1]]));
+            }
+        }
+        triangles
+    }
+}
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Smallest box containing all points, or `None` if there are none
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Aabb> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(
+            Aabb {
+                min: first,
+                max: first,
+            },
+            |aabb, p| Aabb {
+                min: Vec3::new(
+                    aabb.min.x.min(p.x),
+                    aabb.min.y.min(p.y),
+                    aabb.min.z.min(p.z),
+                ),
+                max: Vec3::new(
+                    aabb.max.x.max(p.x),
+                    aabb.max.y.max(p.y),
+                    aabb.max.z.max(p.z),
+                ),
+            },
+        ))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+}
+
+/// Sphere enclosing a set of points
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f64,
+}
+
+impl BoundingSphere {
+    /// Sphere around the center of the points' bounding box, or `None` if there are none
+    pub fn from_points(points: &[Vec3]) -> Option<BoundingSphere> {
+        let center = Aabb::from_points(points.iter().copied())?.center();
+        let radius = points
+            .iter()
+            .map(|&p| (p - center).length())
+            .fold(0.0, f64::max);
+        Some(BoundingSphere { center, radius })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cube_has_twelve_edges_and_triangles() {
+        let cube = Mesh::cube();
+        assert_eq!(cube.edges().len(), 12);
+        assert_eq!(cube.triangles().len(), 12);
+    }
+
+    #[test]
+    fn face_normals_point_outwards() {
+        let cube = Mesh::cube();
+        for (face, indices) in cube.faces.iter().enumerate() {
+            let center = indices
+                .iter()
+                .fold(Vec3::ZERO, |sum, &index| sum + cube.positions[index])
+                / 4.0;
+            let normal = cube.face_normal(&cube.positions, face);
+            // The cube is centered on the origin, so outwards is away from it
+            assert!((normal - center).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn bounds_enclose_points() {
+        let points = [
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(-1.0, 0.0, 5.0),
+            Vec3::new(0.0, 4.0, 4.0),
+        ];
+        let aabb = Aabb::from_points(points).unwrap();
+        assert_eq!(aabb.min, Vec3::new(-1.0, 0.0, 3.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 4.0, 5.0));
+
+        let sphere = BoundingSphere::from_points(&points).unwrap();
+        assert_eq!(sphere.center, Vec3::new(0.0, 2.0, 4.0));
+        assert!(points
+            .iter()
+            .all(|&p| (p - sphere.center).length() <= sphere.radius + 1e-12));
+        assert!(BoundingSphere::from_points(&[]).is_none());
+    }
+}
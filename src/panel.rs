@@ -0,0 +1,409 @@
+use crate::camera::Projection;
+use crate::color::{Palette, MAX_EXPOSURE, MIN_EXPOSURE};
+use crate::history::Snapshot;
+use crate::postprocess::PostEffect;
+use crate::ssao;
+use crate::state::AppState;
+use druid::widget::{
+    Button, Checkbox, Controller, CrossAxisAlignment, Either, Flex, Label, Painter, RadioGroup,
+    Scroll, SizedBox, Slider,
+};
+use druid::{
+    lens, Color, Env, Event, EventCtx, Lens, LensExt, RenderContext, Selector, Widget, WidgetExt,
+};
+
+/// Width of the settings panel in pixels
+const PANEL_WIDTH: f64 = 240.0;
+/// Side of the color swatches in pixels
+const SWATCH_SIZE: f64 = 24.0;
+/// Fastest rotation speed the panel's slider reaches, in radians per second
+const MAX_PANEL_SPEED: f64 = 10.0;
+/// How far from the origin the light position sliders reach
+const LIGHT_RANGE: f64 = 10.0;
+
+/// Sent when the user starts changing a setting in the panel, with the state before
+pub const EDIT_STARTED: Selector<Snapshot> = Selector::new("cube3d.panel.edit-started");
+/// Sent when the user lets go of a setting in the panel
+pub const EDIT_FINISHED: Selector = Selector::new("cube3d.panel.edit-finished");
+
+/// Places the settings panel to the right of `viewport`, shown while `show_panel` is set
+pub fn with_panel(viewport: impl Widget<AppState> + 'static) -> impl Widget<AppState> {
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_flex_child(viewport, 1.0)
+        .with_child(Either::new(
+            |data: &AppState, _env: &Env| data.show_panel,
+            panel(),
+            SizedBox::empty(),
+        ))
+}
+
+/// Scrollable column of sliders, checkboxes and color pickers over the application state
+fn panel() -> impl Widget<AppState> {
+    let zoom = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(|data: &AppState, _env: &Env| {
+            format!("Zoom distance: {:.2}", data.camera.distance())
+        }))
+        .with_child(Slider::new().expand_width().lens(zoom_lens()));
+    let view = section("View")
+        .with_child(zoom)
+        .with_child(slider(
+            "Rotation speed",
+            (0.0, MAX_PANEL_SPEED),
+            rotation_speed_lens(),
+            |value| format!("{:.2} rad/s", value),
+        ))
+        .with_child(checkbox(
+            "Orthographic projection",
+            lens::Map::new(
+                |data: &AppState| data.projection == Projection::Orthographic,
+                |data: &mut AppState, on: bool| {
+                    data.projection = if on {
+                        Projection::Orthographic
+                    } else {
+                        Projection::Perspective
+                    };
+                },
+            ),
+        ))
+        .with_child(checkbox("Debug overlay", AppState::debug));
+
+    let light = section("Light")
+        .with_child(slider(
+            "X",
+            (-LIGHT_RANGE, LIGHT_RANGE),
+            AppState::light_position.then(lens!(crate::math::Vec3, x)),
+            |value| format!("{:.2}", value),
+        ))
+        .with_child(slider(
+            "Y",
+            (-LIGHT_RANGE, LIGHT_RANGE),
+            AppState::light_position.then(lens!(crate::math::Vec3, y)),
+            |value| format!("{:.2}", value),
+        ))
+        .with_child(slider(
+            "Z",
+            (-LIGHT_RANGE, LIGHT_RANGE),
+            AppState::light_position.then(lens!(crate::math::Vec3, z)),
+            |value| format!("{:.2}", value),
+        ))
+        .with_child(slider(
+            "Intensity",
+            (0.0, 3.0),
+            AppState::light_intensity,
+            |value| format!("{:.2}", value),
+        ))
+        .with_child(checkbox("Show light gizmo", AppState::show_light));
+
+    let rendering = section("Rendering")
+        .with_child(checkbox("Wireframe", AppState::wireframe))
+        .with_child(checkbox("Ambient occlusion", AppState::ssao))
+        .with_child(slider(
+            "Occlusion radius",
+            (ssao::MIN_RADIUS, ssao::MAX_RADIUS),
+            AppState::ssao_radius,
+            |value| format!("{:.2}", value),
+        ))
+        .with_child(slider(
+            "Occlusion strength",
+            (0.0, 1.0),
+            AppState::ssao_strength,
+            |value| format!("{:.2}", value),
+        ))
+        .with_child(checkbox("Face ID buffer", AppState::show_ids))
+        .with_child(slider(
+            "Exposure",
+            (MIN_EXPOSURE.ln(), MAX_EXPOSURE.ln()),
+            exposure_lens(),
+            |value| format!("{:.2}", value.exp()),
+        ))
+        .with_child(slider("Opacity", (0.0, 1.0), AppState::opacity, |value| {
+            format!("{:.2}", value)
+        }));
+    let rendering = PostEffect::ALL
+        .iter()
+        .fold(rendering, |column, &effect| {
+            column.with_child(effect_row(effect))
+        })
+        .with_child(Label::new(|data: &AppState, _env: &Env| {
+            let effects: Vec<&str> = data.post_effects.iter().map(|e| e.name()).collect();
+            format!("Chain: {}", effects.join(" > "))
+        }));
+
+    let names = ["Face 1", "Face 2", "Face 3", "Face 4", "Face 5", "Face 6"];
+    let colors = names
+        .iter()
+        .enumerate()
+        .fold(
+            section("Colors").with_child(palette_picker()),
+            |column, (index, &name)| {
+                column.with_child(color_picker(name).lens(AppState::face_colors.index(index)))
+            },
+        )
+        .with_child(color_picker("Background").lens(AppState::background_color))
+        .with_child(color_picker("Gradient bottom").lens(AppState::background_color_bottom))
+        .with_child(color_picker("Fog").lens(AppState::fog_color));
+
+    let column = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(view)
+        .with_child(light)
+        .with_child(rendering)
+        .with_child(colors)
+        .padding(10.0);
+    Scroll::new(column)
+        .vertical()
+        .controller(RecordEdits)
+        .fix_width(PANEL_WIDTH)
+        .background(Color::rgb8(0x24, 0x26, 0x2b))
+}
+
+/// Column starting with a section heading
+fn section(title: &str) -> Flex<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_spacer(6.0)
+        .with_child(Label::new(title).with_text_size(16.0))
+        .with_spacer(4.0)
+}
+
+/// Labelled slider over a value, showing the value as formatted by `display`
+fn slider(
+    name: &'static str,
+    (min, max): (f64, f64),
+    lens: impl Lens<AppState, f64> + 'static,
+    display: fn(f64) -> String,
+) -> impl Widget<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(move |value: &f64, _env: &Env| {
+            format!("{}: {}", name, display(*value))
+        }))
+        .with_child(Slider::new().with_range(min, max).expand_width())
+        .lens(lens)
+}
+
+/// Labelled checkbox over a flag
+fn checkbox(name: &str, lens: impl Lens<AppState, bool> + 'static) -> impl Widget<AppState> {
+    Checkbox::new(name).padding((0.0, 2.0)).lens(lens)
+}
+
+/// Position of the camera between the configured zoom limits, from 0 to 1 on a log scale
+fn zoom_lens() -> impl Lens<AppState, f64> {
+    fn limits(data: &AppState) -> (f64, f64) {
+        let min = data.min_distance.ln();
+        (min, (data.max_distance.ln() - min).max(f64::EPSILON))
+    }
+    fn position(data: &AppState) -> f64 {
+        let (min, range) = limits(data);
+        ((data.camera.distance().ln() - min) / range).clamp(0.0, 1.0)
+    }
+    lens::Map::new(position, |data: &mut AppState, value: f64| {
+        // Every event passes through the lens, and putting the same position back could
+        // still move a camera that is outside the limits
+        if value == position(data) {
+            return;
+        }
+        // Zoom about the target, like the zoom keys
+        let (min, range) = limits(data);
+        let factor = (min + value * range - data.camera.distance().ln()).exp();
+        let target = data.camera.target;
+        let (min, max) = (data.min_distance, data.max_distance);
+        data.camera.zoom(target, factor, min, max);
+    })
+}
+
+/// Length of the angular velocity, keeping its axis
+fn rotation_speed_lens() -> impl Lens<AppState, f64> {
+    lens::Map::new(
+        |data: &AppState| data.angular_velocity.length(),
+        |data: &mut AppState, speed: f64| {
+            let current = data.angular_velocity.length();
+            if speed == current {
+                return;
+            }
+            if current > 0.0 {
+                data.scale_angular_velocity(speed / current);
+            } else {
+                // Start turning around the default axis again
+                let axis = AppState::default().angular_velocity.normalize();
+                data.angular_velocity = axis * speed;
+            }
+        },
+    )
+}
+
+/// Natural log of the exposure, so the slider is even across its range
+fn exposure_lens() -> impl Lens<AppState, f64> {
+    AppState::exposure.map(
+        |exposure| exposure.ln(),
+        |exposure, value| {
+            // Going through the log and back may not give the same exposure
+            if value != exposure.ln() {
+                *exposure = value.exp();
+            }
+        },
+    )
+}
+
+/// Checkbox enabling a post-processing effect, with a button moving it earlier in the chain
+fn effect_row(effect: PostEffect) -> impl Widget<AppState> {
+    let enabled = lens::Map::new(
+        move |data: &AppState| data.post_effects.contains(&effect),
+        move |data: &mut AppState, on: bool| {
+            if on != data.post_effects.contains(&effect) {
+                data.toggle_post_effect(effect);
+            }
+        },
+    );
+    let earlier = Button::new("Earlier")
+        .on_click(move |_ctx, data: &mut AppState, _env| data.move_post_effect_earlier(effect))
+        .disabled_if(move |data: &AppState, _env| !data.post_effects.contains(&effect));
+    Flex::row()
+        .with_flex_child(checkbox(effect.name(), enabled).expand_width(), 1.0)
+        .with_child(earlier)
+}
+
+/// Radio buttons switching the face colors to a preset palette
+fn palette_picker() -> impl Widget<AppState> {
+    RadioGroup::column(Palette::ALL.map(|palette| (palette.name(), palette))).lens(palette_lens())
+}
+
+/// Current palette, switching to a newly picked one
+fn palette_lens() -> impl Lens<AppState, Palette> {
+    lens::Map::new(
+        |data: &AppState| data.palette,
+        |data: &mut AppState, palette: Palette| {
+            // Every event and command passes through the lens, so only a new pick resets colors
+            if palette != data.palette {
+                data.set_palette(palette);
+            }
+        },
+    )
+}
+
+/// Swatch with red, green and blue sliders
+fn color_picker(name: &str) -> impl Widget<Color> {
+    let swatch = Painter::new(|ctx, color: &Color, _env| {
+        let rect = ctx.size().to_rect();
+        ctx.fill(rect, color);
+        ctx.stroke(rect, &Color::grey(0.6), 1.0);
+    })
+    .fix_size(SWATCH_SIZE, SWATCH_SIZE);
+    let channels = (0..3).fold(Flex::column(), |column, channel| {
+        column.with_child(
+            Slider::new()
+                .with_range(0.0, 255.0)
+                .expand_width()
+                .lens(color_channel(channel)),
+        )
+    });
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_spacer(4.0)
+        .with_child(Label::new(name))
+        .with_child(
+            Flex::row()
+                .with_child(swatch)
+                .with_spacer(8.0)
+                .with_flex_child(channels, 1.0),
+        )
+}
+
+/// Red (0), green (1) or blue (2) channel of a color, from 0 to 255
+fn color_channel(channel: usize) -> impl Lens<Color, f64> {
+    lens::Map::new(
+        move |color: &Color| {
+            let (r, g, b, _) = color.as_rgba8();
+            [r, g, b][channel] as f64
+        },
+        move |color: &mut Color, value: f64| {
+            let (r, g, b, a) = color.as_rgba8();
+            let mut rgb = [r, g, b];
+            rgb[channel] = value.round() as u8;
+            *color = Color::rgba8(rgb[0], rgb[1], rgb[2], a);
+        },
+    )
+}
+
+/// Tells the cube widget when a change made in the panel starts and ends, so that a
+/// whole slider drag is undone in one step
+struct RecordEdits;
+
+impl<W: Widget<AppState>> Controller<AppState, W> for RecordEdits {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        if let Event::MouseDown(_) = event {
+            ctx.submit_command(EDIT_STARTED.with(Snapshot::capture(data)));
+        }
+        child.event(ctx, event, data, env);
+        if let Event::MouseUp(_) = event {
+            ctx.submit_command(EDIT_FINISHED);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_sliders_change_one_channel() {
+        let mut color = Color::rgba8(10, 20, 30, 200);
+        let green = color_channel(1);
+        assert_eq!(green.get(&color), 20.0);
+        green.put(&mut color, 99.6);
+        assert_eq!(color.as_rgba8(), (10, 100, 30, 200));
+    }
+
+    #[test]
+    fn events_keep_edited_face_colors() {
+        let mut data = AppState::default();
+        data.face_colors[0] = Color::WHITE;
+        let palette = palette_lens();
+        // Commands and mouse moves reach the radio group without changing its value
+        palette.with_mut(&mut data, |_| ());
+        assert_eq!(data.face_colors[0], Color::WHITE);
+
+        palette.with_mut(&mut data, |palette| *palette = Palette::Pastel);
+        assert_eq!(data.face_colors, Palette::Pastel.colors());
+    }
+
+    #[test]
+    fn events_leave_unchanged_sliders_alone() {
+        let mut data = AppState {
+            exposure: 0.3,
+            angular_velocity: crate::math::Vec3::new(0.1, 0.7, 0.3),
+            ..AppState::default()
+        };
+        // A camera outside the zoom limits stays where it is until the slider moves
+        data.max_distance = data.camera.distance() / 2.0;
+        let before = (data.exposure, data.angular_velocity, data.camera);
+        exposure_lens().with_mut(&mut data, |_| ());
+        rotation_speed_lens().with_mut(&mut data, |_| ());
+        zoom_lens().with_mut(&mut data, |_| ());
+        assert_eq!((data.exposure, data.angular_velocity, data.camera), before);
+    }
+
+    #[test]
+    fn zoom_slider_spans_the_configured_limits() {
+        let mut data = AppState {
+            min_distance: 2.0,
+            max_distance: 8.0,
+            ..AppState::default()
+        };
+        let zoom = zoom_lens();
+        zoom.put(&mut data, 1.0);
+        assert!((data.camera.distance() - 8.0).abs() < 1e-9);
+        zoom.put(&mut data, 0.5);
+        assert!((data.camera.distance() - 4.0).abs() < 1e-9);
+        assert!((zoom.get(&data) - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::math::Vec3;
+use crate::mesh::Mesh;
+
+/// Determinants below this are treated as rays parallel to the triangle
+const PARALLEL_EPSILON: f64 = 1e-12;
+
+/// Half-line starting at `origin` and running along `direction`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// Ray from `origin` through `point`
+    pub fn through(origin: Vec3, point: Vec3) -> Ray {
+        Ray {
+            origin,
+            direction: (point - origin).normalize(),
+        }
+    }
+
+    /// Point at `distance` along the ray
+    pub fn at(&self, distance: f64) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Intersects the ray with triangle `abc` using the Möller–Trumbore algorithm.
+    ///
+    /// Both sides of the triangle are hit. Returns the distance along the ray and the
+    /// barycentric weights of `a`, `b` and `c` at the hit point.
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<(f64, Vec3)> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let det = ab.dot(p);
+        if det.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(ab);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = ac.dot(q) * inv_det;
+        (distance >= 0.0).then(|| (distance, Vec3::new(1.0 - u - v, u, v)))
+    }
+}
+
+/// Nearest intersection of a ray with a mesh
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Index of the face that was hit
+    pub face: usize,
+    /// Vertex indices of the triangle of that face that was hit
+    pub triangle: [usize; 3],
+    /// Distance along the ray
+    pub distance: f64,
+    /// Barycentric weights of the triangle's vertices at the hit point
+    pub barycentric: Vec3,
+    /// Hit point, in the same space as the positions
+    pub position: Vec3,
+}
+
+/// Casts a ray against a mesh whose vertices have been moved to `positions`.
+///
+/// Returns the visible (nearest) hit, if any.
+pub fn pick(ray: &Ray, mesh: &Mesh, positions: &[Vec3]) -> Option<Hit> {
+    mesh.triangles()
+        .into_iter()
+        .filter_map(|(face, triangle)| {
+            let [a, b, c] = triangle.map(|index| positions[index]);
+            let (distance, barycentric) = ray.intersect_triangle(a, b, c)?;
+            Some(Hit {
+                face,
+                triangle,
+                distance,
+                barycentric,
+                position: ray.at(distance),
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_hits_triangle_at_barycentric_point() {
+        let (a, b, c) = (
+            Vec3::new(0.0, 0.0, 5.0),
+            Vec3::new(4.0, 0.0, 5.0),
+            Vec3::new(0.0, 4.0, 5.0),
+        );
+        let ray = Ray::through(Vec3::ZERO, Vec3::new(1.0, 2.0, 5.0));
+        let (distance, weights) = ray.intersect_triangle(a, b, c).unwrap();
+        let point = a * weights.x + b * weights.y + c * weights.z;
+        assert!((point - Vec3::new(1.0, 2.0, 5.0)).length() < 1e-9);
+        assert!((ray.at(distance) - point).length() < 1e-9);
+        // Misses outside the triangle and behind the origin
+        assert!(Ray::through(Vec3::ZERO, Vec3::new(3.0, 3.0, 5.0))
+            .intersect_triangle(a, b, c)
+            .is_none());
+        assert!(Ray::through(Vec3::ZERO, Vec3::new(1.0, 1.0, -5.0))
+            .intersect_triangle(a, b, c)
+            .is_none());
+    }
+
+    #[test]
+    fn pick_returns_nearest_face() {
+        let cube = Mesh::cube();
+        let ray = Ray::through(Vec3::new(0.2, 0.3, -5.0), Vec3::new(0.2, 0.3, 0.0));
+        let hit = pick(&ray, &cube, &cube.positions).unwrap();
+        // Face 0 lies on the z = -1 side facing the ray's origin
+        assert_eq!(hit.face, 0);
+        assert!((hit.distance - 4.0).abs() < 1e-9);
+        assert!((hit.position - Vec3::new(0.2, 0.3, -1.0)).length() < 1e-9);
+        let missed = Ray::through(Vec3::new(3.0, 0.0, -5.0), Vec3::new(3.0, 0.0, 0.0));
+        assert!(pick(&missed, &cube, &cube.positions).is_none());
+    }
+}
@@ -0,0 +1,361 @@
+use crate::graphics::FrameBuffer;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+/// Luminance threshold above which pixels contribute to bloom
+const BLOOM_THRESHOLD: f64 = 0.8;
+/// Blur radius of the bloom halo in pixels
+const BLOOM_RADIUS: usize = 6;
+/// Strength of the bloom halo added back to the frame
+const BLOOM_STRENGTH: f64 = 0.6;
+/// Minimum local contrast for FXAA to treat a pixel as an edge
+const FXAA_EDGE_THRESHOLD: f64 = 0.125;
+/// How far the vignette darkens the corners (0.0 - 1.0)
+const VIGNETTE_STRENGTH: f64 = 0.6;
+/// Relative depth jump that counts as an outline edge
+const OUTLINE_DEPTH_THRESHOLD: f64 = 0.1;
+
+/// A full-screen effect applied to the frame after rasterization
+#[derive(Clone, Copy, Data, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostEffect {
+    /// Glow around bright areas
+    Bloom,
+    /// Fast approximate anti-aliasing
+    Fxaa,
+    /// Darkens the frame towards its corners
+    Vignette,
+    /// Draws outlines at depth discontinuities
+    Outline,
+    /// Converts the frame to grayscale
+    Grayscale,
+}
+
+impl PostEffect {
+    /// Every effect, in the order of their toggle keys
+    pub const ALL: [PostEffect; 5] = [
+        PostEffect::Bloom,
+        PostEffect::Fxaa,
+        PostEffect::Vignette,
+        PostEffect::Outline,
+        PostEffect::Grayscale,
+    ];
+
+    /// Human-readable name of the effect
+    pub fn name(self) -> &'static str {
+        match self {
+            PostEffect::Bloom => "Bloom",
+            PostEffect::Fxaa => "FXAA",
+            PostEffect::Vignette => "Vignette",
+            PostEffect::Outline => "Outline",
+            PostEffect::Grayscale => "Grayscale",
+        }
+    }
+
+    /// Applies the effect to the frame in place
+    pub fn apply(self, frame: &mut FrameBuffer) {
+        match self {
+            PostEffect::Bloom => bloom(frame),
+            PostEffect::Fxaa => fxaa(frame),
+            PostEffect::Vignette => vignette(frame),
+            PostEffect::Outline => outline(frame),
+            PostEffect::Grayscale => grayscale(frame),
+        }
+    }
+}
+
+/// Runs the effects over the frame in list order
+pub fn apply_effects(effects: &[PostEffect], frame: &mut FrameBuffer) {
+    for effect in effects {
+        effect.apply(frame);
+    }
+}
+
+/// Rec. 709 luminance of a linear color
+fn luminance(color: &[f64; 4]) -> f64 {
+    0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+}
+
+/// Separable box blur of an RGBA image
+fn box_blur(image: &[[f64; 4]], width: usize, height: usize, radius: usize) -> Vec<[f64; 4]> {
+    let mut horizontal = vec![[0.0; 4]; image.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius).min(width - 1));
+            let mut sum = [0.0; 4];
+            for sx in x0..=x1 {
+                let pixel = image[y * width + sx];
+                (0..4).for_each(|i| sum[i] += pixel[i]);
+            }
+            let count = (x1 - x0 + 1) as f64;
+            horizontal[y * width + x] = sum.map(|c| c / count);
+        }
+    }
+
+    let mut blurred = vec![[0.0; 4]; image.len()];
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius).min(height - 1));
+        for x in 0..width {
+            let mut sum = [0.0; 4];
+            for sy in y0..=y1 {
+                let pixel = horizontal[sy * width + x];
+                (0..4).for_each(|i| sum[i] += pixel[i]);
+            }
+            let count = (y1 - y0 + 1) as f64;
+            blurred[y * width + x] = sum.map(|c| c / count);
+        }
+    }
+    blurred
+}
+
+/// Extracts bright pixels, blurs them and adds the halo back onto the frame
+fn bloom(frame: &mut FrameBuffer) {
+    if frame.width == 0 || frame.height == 0 {
+        return;
+    }
+    let bright: Vec<[f64; 4]> = frame
+        .color
+        .iter()
+        .map(|color| {
+            let excess = luminance(color) - BLOOM_THRESHOLD;
+            if excess <= 0.0 {
+                return [0.0; 4];
+            }
+            let scale = excess / luminance(color);
+            [
+                color[0] * scale,
+                color[1] * scale,
+                color[2] * scale,
+                color[3] * scale,
+            ]
+        })
+        .collect();
+
+    // Two box blur passes approximate a gaussian
+    let halo = box_blur(&bright, frame.width, frame.height, BLOOM_RADIUS);
+    let halo = box_blur(&halo, frame.width, frame.height, BLOOM_RADIUS);
+    for (color, glow) in frame.color.iter_mut().zip(halo.iter()) {
+        for i in 0..4 {
+            color[i] += glow[i] * BLOOM_STRENGTH;
+        }
+        color[3] = color[3].min(1.0);
+    }
+}
+
+/// Simplified FXAA: blends high-contrast pixels with their neighbours across the edge
+fn fxaa(frame: &mut FrameBuffer) {
+    let (width, height) = (frame.width, frame.height);
+    if width < 3 || height < 3 {
+        return;
+    }
+    // Work on perceptual luma so dark and bright edges are treated alike
+    let luma: Vec<f64> = frame
+        .color
+        .iter()
+        .map(|color| (luminance(color) / (1.0 + luminance(color))).sqrt())
+        .collect();
+    let source = frame.color.clone();
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let offset = y * width + x;
+            let center = luma[offset];
+            let north = luma[offset - width];
+            let south = luma[offset + width];
+            let west = luma[offset - 1];
+            let east = luma[offset + 1];
+
+            let max = center.max(north).max(south).max(west).max(east);
+            let min = center.min(north).min(south).min(west).min(east);
+            let contrast = max - min;
+            if contrast < FXAA_EDGE_THRESHOLD.max(max * 0.125) {
+                continue;
+            }
+
+            // Blend across the edge: vertically for horizontal edges and vice versa
+            let horizontal_edge = (north + south - 2.0 * center).abs();
+            let vertical_edge = (west + east - 2.0 * center).abs();
+            let (a, b) = if horizontal_edge >= vertical_edge {
+                (offset - width, offset + width)
+            } else {
+                (offset - 1, offset + 1)
+            };
+
+            // Subpixel blend factor grows with the local contrast
+            let average = (north + south + west + east) * 0.25;
+            let blend = ((average - center).abs() / contrast).clamp(0.0, 1.0) * 0.5;
+            let color = &mut frame.color[offset];
+            for i in 0..4 {
+                let neighbours = (source[a][i] + source[b][i]) * 0.5;
+                color[i] = source[offset][i] * (1.0 - blend) + neighbours * blend;
+            }
+        }
+    }
+}
+
+/// Darkens pixels with their distance from the frame center
+fn vignette(frame: &mut FrameBuffer) {
+    let (width, height) = (frame.width as f64, frame.height as f64);
+    for (offset, color) in frame.color.iter_mut().enumerate() {
+        let x = (offset % frame.width) as f64 + 0.5;
+        let y = (offset / frame.width) as f64 + 0.5;
+        let dx = (x / width - 0.5) * 2.0;
+        let dy = (y / height - 0.5) * 2.0;
+        let distance = ((dx * dx + dy * dy) / 2.0).sqrt();
+        // Smoothstep falloff starting a third of the way out
+        let t = ((distance - 0.3) / 0.7).clamp(0.0, 1.0);
+        let factor = 1.0 - VIGNETTE_STRENGTH * t * t * (3.0 - 2.0 * t);
+        for channel in color.iter_mut().take(3) {
+            *channel *= factor;
+        }
+    }
+}
+
+/// Draws black outlines where the depth jumps between neighbouring pixels
+fn outline(frame: &mut FrameBuffer) {
+    let (width, height) = (frame.width, frame.height);
+    let (near, far) = frame
+        .depth
+        .iter()
+        .filter(|depth| depth.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(near, far), &depth| {
+            (near.min(depth), far.max(depth))
+        });
+    let threshold = ((far - near) * OUTLINE_DEPTH_THRESHOLD).max(f64::EPSILON);
+
+    let is_edge = |x: usize, y: usize| {
+        let depth = frame.depth[y * width + x];
+        let neighbours = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        neighbours
+            .iter()
+            .filter(|&&(nx, ny)| nx < width && ny < height)
+            .any(|&(nx, ny)| {
+                let other = frame.depth[ny * width + nx];
+                // Silhouettes against the background always count as edges
+                if depth.is_finite() != other.is_finite() {
+                    return depth.is_finite();
+                }
+                depth.is_finite() && depth - other > threshold
+            })
+    };
+
+    let edges: Vec<bool> = (0..width * height)
+        .map(|offset| is_edge(offset % width, offset / width))
+        .collect();
+    for (color, edge) in frame.color.iter_mut().zip(edges) {
+        if edge {
+            *color = [0.0, 0.0, 0.0, 1.0];
+        }
+    }
+}
+
+/// Replaces every pixel with its luminance
+fn grayscale(frame: &mut FrameBuffer) {
+    for color in frame.color.iter_mut() {
+        let luma = luminance(color);
+        color[0] = luma;
+        color[1] = luma;
+        color[2] = luma;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 21;
+
+    /// Frame filled with one opaque color at depth 1.0
+    fn filled(color: [f64; 3]) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(SIZE, SIZE);
+        for offset in 0..SIZE * SIZE {
+            frame.set_pixel(offset, [color[0], color[1], color[2], 1.0]);
+            frame.depth[offset] = 1.0;
+        }
+        frame
+    }
+
+    fn at(frame: &FrameBuffer, x: usize, y: usize) -> [f64; 4] {
+        frame.color[y * frame.width + x]
+    }
+
+    #[test]
+    fn grayscale_keeps_luminance() {
+        let mut frame = filled([0.2, 0.6, 0.1]);
+        let before = luminance(&at(&frame, 0, 0));
+        grayscale(&mut frame);
+        let [r, g, b, _] = at(&frame, 0, 0);
+        assert_eq!((r, g), (g, b));
+        assert!((r - before).abs() < 1e-12);
+    }
+
+    #[test]
+    fn vignette_darkens_corners_only() {
+        let mut frame = filled([1.0, 1.0, 1.0]);
+        vignette(&mut frame);
+        assert_eq!(at(&frame, SIZE / 2, SIZE / 2)[0], 1.0);
+        assert!(at(&frame, 0, 0)[0] < 0.5);
+    }
+
+    #[test]
+    fn bloom_spreads_bright_pixels() {
+        let mut frame = filled([0.0, 0.0, 0.0]);
+        frame.set_pixel(SIZE / 2 * SIZE + SIZE / 2, [20.0, 20.0, 20.0, 1.0]);
+        bloom(&mut frame);
+        // The halo fades with distance from the bright pixel
+        let near = at(&frame, SIZE / 2 + 3, SIZE / 2)[0];
+        assert!(near > 0.0);
+        assert!(at(&frame, 0, 0)[0] < near);
+    }
+
+    #[test]
+    fn fxaa_softens_hard_edges() {
+        let mut frame = filled([0.0, 0.0, 0.0]);
+        for y in 0..SIZE {
+            for x in SIZE / 2..SIZE {
+                frame.set_pixel(y * SIZE + x, [1.0, 1.0, 1.0, 1.0]);
+            }
+        }
+        fxaa(&mut frame);
+        let edge = at(&frame, SIZE / 2, SIZE / 2)[0];
+        assert!(edge > 0.0 && edge < 1.0, "edge pixel {}", edge);
+        assert_eq!(at(&frame, 2, SIZE / 2)[0], 0.0);
+    }
+
+    #[test]
+    fn outline_traces_silhouettes() {
+        let mut frame = filled([1.0, 1.0, 1.0]);
+        // Only a square in the middle is covered by geometry
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                if !(5..16).contains(&x) || !(5..16).contains(&y) {
+                    frame.depth[y * SIZE + x] = f64::INFINITY;
+                }
+            }
+        }
+        outline(&mut frame);
+        assert_eq!(at(&frame, 5, 10), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(at(&frame, 10, 10)[0], 1.0);
+        assert_eq!(at(&frame, 2, 10)[0], 1.0);
+    }
+
+    #[test]
+    fn effects_run_in_chain_order() {
+        let render = |effects: &[PostEffect]| {
+            let mut frame = filled([2.0, 2.0, 2.0]);
+            frame.depth[0] = f64::INFINITY;
+            apply_effects(effects, &mut frame);
+            frame
+        };
+        // Outlines drawn last stay black, while bloom run afterwards glows over them
+        let outlined_last = render(&[PostEffect::Bloom, PostEffect::Outline]);
+        let bloomed_last = render(&[PostEffect::Outline, PostEffect::Bloom]);
+        assert_eq!(at(&outlined_last, 1, 0)[0], 0.0);
+        assert!(at(&bloomed_last, 1, 0)[0] > 0.0);
+    }
+}
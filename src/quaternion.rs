@@ -0,0 +1,297 @@
+use crate::math::{Mat3, Mat4, Vec3};
+use druid::Data;
+use serde::{Deserialize, Serialize};
+use std::ops::{Mul, MulAssign, Neg};
+
+/// Unit quaternion representing a 3D orientation.
+///
+/// Rotations compose right to left like matrices: `a * b` applies `b` first.
+#[derive(Clone, Copy, Debug, PartialEq, Data, Serialize, Deserialize)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat::new(1.0, 0.0, 0.0, 0.0);
+
+    pub const fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Quat { w, x, y, z }
+    }
+
+    /// Rotation of `angle` radians around `axis`
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Self {
+        let length = axis.length();
+        if length == 0.0 {
+            return Quat::IDENTITY;
+        }
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        let axis = axis * (sin / length);
+        Quat::new(cos, axis.x, axis.y, axis.z)
+    }
+
+    /// Rotation equivalent to an orthonormal rotation matrix
+    pub fn from_mat3(m: Mat3) -> Self {
+        let r = m.rows;
+        let trace = r[0][0] + r[1][1] + r[2][2];
+        // Pick the largest component first to keep the division well conditioned
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat::new(
+                0.25 * s,
+                (r[2][1] - r[1][2]) / s,
+                (r[0][2] - r[2][0]) / s,
+                (r[1][0] - r[0][1]) / s,
+            )
+        } else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+            let s = (1.0 + r[0][0] - r[1][1] - r[2][2]).sqrt() * 2.0;
+            Quat::new(
+                (r[2][1] - r[1][2]) / s,
+                0.25 * s,
+                (r[0][1] + r[1][0]) / s,
+                (r[0][2] + r[2][0]) / s,
+            )
+        } else if r[1][1] > r[2][2] {
+            let s = (1.0 + r[1][1] - r[0][0] - r[2][2]).sqrt() * 2.0;
+            Quat::new(
+                (r[0][2] - r[2][0]) / s,
+                (r[0][1] + r[1][0]) / s,
+                0.25 * s,
+                (r[1][2] + r[2][1]) / s,
+            )
+        } else {
+            let s = (1.0 + r[2][2] - r[0][0] - r[1][1]).sqrt() * 2.0;
+            Quat::new(
+                (r[1][0] - r[0][1]) / s,
+                (r[0][2] + r[2][0]) / s,
+                (r[1][2] + r[2][1]) / s,
+                0.25 * s,
+            )
+        };
+        q.normalize()
+    }
+
+    /// Returns the rotation axis and angle in radians (0.0 to PI for the shortest arc)
+    pub fn to_axis_angle(self) -> (Vec3, f64) {
+        let q = if self.w < 0.0 { -self } else { self };
+        let sin = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if sin < 1e-12 {
+            return (Vec3::X, 0.0);
+        }
+        (
+            Vec3::new(q.x, q.y, q.z) / sin,
+            2.0 * q.w.clamp(-1.0, 1.0).acos(),
+        )
+    }
+
+    /// Rotation from Euler angles in radians, applied around X, then Y, then Z
+    pub fn from_euler(angles: Vec3) -> Self {
+        Quat::from_axis_angle(Vec3::Z, angles.z)
+            * Quat::from_axis_angle(Vec3::Y, angles.y)
+            * Quat::from_axis_angle(Vec3::X, angles.x)
+    }
+
+    /// Euler angles in radians matching [`Quat::from_euler`]
+    pub fn to_euler(self) -> Vec3 {
+        let Quat { w, x, y, z } = self;
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+        Vec3::new(roll, pitch, yaw)
+    }
+
+    pub fn dot(self, rhs: Quat) -> f64 {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// Rescales to unit length, countering drift from repeated composition
+    pub fn normalize(self) -> Quat {
+        let length = self.length();
+        if length == 0.0 {
+            return Quat::IDENTITY;
+        }
+        Quat::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    /// Inverse rotation of a unit quaternion
+    pub fn conjugate(self) -> Quat {
+        Quat::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Rotates a vector
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+
+    /// Spherical linear interpolation along the shortest arc
+    pub fn slerp(self, other: Quat, t: f64) -> Quat {
+        let mut cos = self.dot(other);
+        // q and -q are the same rotation; flip to take the shorter path
+        let other = if cos < 0.0 {
+            cos = -cos;
+            -other
+        } else {
+            other
+        };
+        if cos > 0.9995 {
+            // Nearly parallel: fall back to a normalized lerp
+            return Quat::new(
+                self.w + (other.w - self.w) * t,
+                self.x + (other.x - self.x) * t,
+                self.y + (other.y - self.y) * t,
+                self.z + (other.z - self.z) * t,
+            )
+            .normalize();
+        }
+        let theta = cos.acos();
+        let sin = theta.sin();
+        let a = ((1.0 - t) * theta).sin() / sin;
+        let b = (t * theta).sin() / sin;
+        Quat::new(
+            self.w * a + other.w * b,
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+        )
+    }
+
+    /// Equivalent rotation matrix
+    pub fn to_mat3(self) -> Mat3 {
+        let Quat { w, x, y, z } = self;
+        Mat3::from_rows([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ])
+    }
+
+    /// Equivalent homogeneous rotation matrix
+    pub fn to_mat4(self) -> Mat4 {
+        Mat4::from_mat3(self.to_mat3())
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+    fn neg(self) -> Quat {
+        Quat::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+    /// Hamilton product
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+impl MulAssign for Quat {
+    fn mul_assign(&mut self, rhs: Quat) {
+        *self = *self * rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::tests::{rotation_x, rotation_y, rotation_z};
+    use std::f64::consts::FRAC_PI_2;
+
+    fn assert_vec_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotate_matches_matrix() {
+        let q = Quat::from_euler(Vec3::new(0.3, -1.1, 2.0));
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert_vec_eq(q.rotate(v), q.to_mat3() * v);
+        assert_vec_eq(
+            Quat::from_axis_angle(Vec3::Z, FRAC_PI_2).rotate(Vec3::X),
+            Vec3::Y,
+        );
+    }
+
+    #[test]
+    fn matrix_round_trips() {
+        for angles in [
+            Vec3::new(0.3, -1.1, 2.0),
+            Vec3::new(3.1, 0.0, 0.0),
+            Vec3::new(0.0, 3.1, 0.0),
+            Vec3::new(0.0, 0.0, -3.1),
+        ] {
+            let q = Quat::from_euler(angles);
+            let v = Vec3::new(1.0, -2.0, 0.5);
+            assert_vec_eq(Quat::from_mat3(q.to_mat3()).rotate(v), q.rotate(v));
+        }
+    }
+
+    #[test]
+    fn euler_round_trips() {
+        let angles = Vec3::new(0.4, -0.7, 1.3);
+        assert_vec_eq(Quat::from_euler(angles).to_euler(), angles);
+        // Matches the matrix order rotation_z * rotation_y * rotation_x
+        let m = rotation_z(angles.z) * rotation_y(angles.y) * rotation_x(angles.x);
+        let v = Vec3::new(-2.0, 0.5, 1.0);
+        assert_vec_eq(Quat::from_euler(angles).rotate(v), m * v);
+    }
+
+    #[test]
+    fn axis_angle_round_trips() {
+        let axis = Vec3::new(1.0, 2.0, -2.0).normalize();
+        let (a, angle) = Quat::from_axis_angle(axis, 2.5).to_axis_angle();
+        assert_vec_eq(a, axis);
+        assert!((angle - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn slerp_interpolates_along_shortest_arc() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        let half = a.slerp(b, 0.5);
+        assert_vec_eq(
+            half.rotate(Vec3::X),
+            Quat::from_axis_angle(Vec3::Y, FRAC_PI_2 / 2.0).rotate(Vec3::X),
+        );
+        // The negated endpoint is the same rotation and must give the same result
+        assert_vec_eq(a.slerp(-b, 0.5).rotate(Vec3::X), half.rotate(Vec3::X));
+        assert_vec_eq(a.slerp(b, 1.0).rotate(Vec3::Z), b.rotate(Vec3::Z));
+    }
+}
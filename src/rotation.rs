@@ -0,0 +1,117 @@
+use crate::math::{Vec2, Vec3};
+use crate::quaternion::Quat;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+/// Radians of rotation per pixel of mouse movement in the turntable and screen-axes modes
+const DRAG_SENSITIVITY: f64 = 0.01;
+
+/// How mouse drags are turned into rotations
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationMode {
+    /// Shoemake arcball: the point under the cursor follows it across a virtual sphere
+    Arcball,
+    /// Horizontal drags spin around the cube's up axis, vertical drags tilt it
+    Turntable,
+    /// Horizontal and vertical drags rotate around the screen's Y and X axes.
+    ///
+    /// Saved as `legacy` before it was named for what it does.
+    #[serde(alias = "legacy")]
+    ScreenAxes,
+}
+
+impl RotationMode {
+    /// Returns the next mode in the cycle
+    pub fn next(self) -> Self {
+        match self {
+            RotationMode::Arcball => RotationMode::Turntable,
+            RotationMode::Turntable => RotationMode::ScreenAxes,
+            RotationMode::ScreenAxes => RotationMode::Arcball,
+        }
+    }
+
+    /// Human-readable name of the mode
+    pub fn name(self) -> &'static str {
+        match self {
+            RotationMode::Arcball => "Arcball",
+            RotationMode::Turntable => "Turntable",
+            RotationMode::ScreenAxes => "Screen axes",
+        }
+    }
+
+    /// Returns the view-space rotation of the scene for a mouse drag from `from` to `to`.
+    ///
+    /// `center` and `radius` describe the arcball sphere in screen coordinates and
+    /// `up` is the world's up direction in view space, used by the turntable.
+    pub fn drag(self, from: Vec2, to: Vec2, center: Vec2, radius: f64, up: Vec3) -> Quat {
+        let delta = to - from;
+        match self {
+            RotationMode::Arcball => {
+                let a = arcball_point(from, center, radius);
+                let b = arcball_point(to, center, radius);
+                let angle = a.dot(b).clamp(-1.0, 1.0).acos();
+                Quat::from_axis_angle(a.cross(b), angle)
+            }
+            RotationMode::Turntable => {
+                // Spin around the world's up axis, then tilt around the screen's X axis
+                let spin = Quat::from_axis_angle(up, delta.x * DRAG_SENSITIVITY);
+                let tilt = Quat::from_axis_angle(Vec3::X, delta.y * DRAG_SENSITIVITY);
+                tilt * spin
+            }
+            RotationMode::ScreenAxes => {
+                Quat::from_axis_angle(Vec3::Y, delta.x * DRAG_SENSITIVITY)
+                    * Quat::from_axis_angle(Vec3::X, delta.y * DRAG_SENSITIVITY)
+            }
+        }
+    }
+}
+
+/// Maps a screen point onto the front half of the unit arcball sphere.
+///
+/// Points outside the sphere are pulled onto its silhouette, so dragging
+/// around the outside rolls the cube around the view axis.
+fn arcball_point(point: Vec2, center: Vec2, radius: f64) -> Vec3 {
+    let p = (point - center) / radius;
+    let d = p.length_squared();
+    if d <= 1.0 {
+        // The viewer looks down +z, so the visible hemisphere faces -z
+        Vec3::new(p.x, p.y, -(1.0 - d).sqrt())
+    } else {
+        p.extend(0.0).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arcball_moves_grabbed_point_to_cursor() {
+        let (center, radius) = (Vec2::new(100.0, 100.0), 50.0);
+        let (from, to) = (Vec2::new(110.0, 95.0), Vec2::new(130.0, 120.0));
+        let rotation = RotationMode::Arcball.drag(from, to, center, radius, Vec3::Y);
+        let grabbed = arcball_point(from, center, radius);
+        let target = arcball_point(to, center, radius);
+        assert!((rotation.rotate(grabbed) - target).length() < 1e-9);
+    }
+
+    #[test]
+    fn turntable_spins_around_up_axis() {
+        let up = Vec3::new(0.0, -0.8, 0.6);
+        let rotation =
+            RotationMode::Turntable.drag(Vec2::ZERO, Vec2::new(23.0, 0.0), Vec2::ZERO, 1.0, up);
+        // Horizontal drags alone never tilt the up axis
+        assert!((rotation.rotate(up) - up).length() < 1e-9);
+    }
+
+    #[test]
+    fn legacy_settings_load_as_screen_axes() {
+        #[derive(Deserialize)]
+        struct Entry {
+            mode: RotationMode,
+        }
+        let entry: Entry = toml::from_str("mode = \"legacy\"").unwrap();
+        assert!(entry.mode == RotationMode::ScreenAxes);
+    }
+}
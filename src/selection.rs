@@ -0,0 +1,132 @@
+use crate::math::Vec3;
+use crate::mesh::Mesh;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+
+/// Kind of mesh element that clicking selects
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionMode {
+    Face,
+    Edge,
+    Vertex,
+}
+
+impl SelectionMode {
+    /// Returns the next mode in the cycle
+    pub fn next(self) -> Self {
+        match self {
+            SelectionMode::Face => SelectionMode::Edge,
+            SelectionMode::Edge => SelectionMode::Vertex,
+            SelectionMode::Vertex => SelectionMode::Face,
+        }
+    }
+
+    /// Human-readable name of the mode
+    pub fn name(self) -> &'static str {
+        match self {
+            SelectionMode::Face => "Face",
+            SelectionMode::Edge => "Edge",
+            SelectionMode::Vertex => "Vertex",
+        }
+    }
+
+    /// Returns the element of this kind closest to `point` on face `face`.
+    ///
+    /// Edges are indices into [`Mesh::edges`]; only elements of that face are considered.
+    pub fn element_at(self, mesh: &Mesh, positions: &[Vec3], face: usize, point: Vec3) -> usize {
+        let indices = &mesh.faces[face];
+        let distance_to = |index: usize| (positions[index] - point).length();
+        match self {
+            SelectionMode::Face => face,
+            SelectionMode::Edge => {
+                let edges = mesh.edges();
+                let boundary = (0..indices.len()).map(|i| {
+                    let (a, b) = (indices[i], indices[(i + 1) % indices.len()]);
+                    (a.min(b), a.max(b))
+                });
+                let nearest = boundary
+                    .min_by(|&(a0, b0), &(a1, b1)| {
+                        let d0 = distance_to_segment(point, positions[a0], positions[b0]);
+                        let d1 = distance_to_segment(point, positions[a1], positions[b1]);
+                        d0.total_cmp(&d1)
+                    })
+                    .unwrap();
+                edges.iter().position(|&edge| edge == nearest).unwrap()
+            }
+            SelectionMode::Vertex => *indices
+                .iter()
+                .min_by(|&&a, &&b| distance_to(a).total_cmp(&distance_to(b)))
+                .unwrap(),
+        }
+    }
+
+    /// Vertex indices of an element
+    pub fn vertices(self, mesh: &Mesh, element: usize) -> Vec<usize> {
+        match self {
+            SelectionMode::Face => mesh.faces[element].clone(),
+            SelectionMode::Edge => {
+                let (a, b) = mesh.edges()[element];
+                vec![a, b]
+            }
+            SelectionMode::Vertex => vec![element],
+        }
+    }
+
+    /// Unit normal and position of an element, in the space of `positions`.
+    ///
+    /// Edges and vertices use the average normal of the faces they belong to and
+    /// edges are located at their midpoint, faces at their center.
+    pub fn describe(self, mesh: &Mesh, positions: &[Vec3], element: usize) -> (Vec3, Vec3) {
+        let vertices = self.vertices(mesh, element);
+        let normal = match self {
+            SelectionMode::Face => mesh.face_normal(positions, element),
+            SelectionMode::Edge | SelectionMode::Vertex => (0..mesh.faces.len())
+                .filter(|&face| vertices.iter().all(|v| mesh.faces[face].contains(v)))
+                .fold(Vec3::ZERO, |sum, face| {
+                    sum + mesh.face_normal(positions, face)
+                })
+                .normalize(),
+        };
+        let sum = vertices
+            .iter()
+            .fold(Vec3::ZERO, |sum, &index| sum + positions[index]);
+        (normal, sum / vertices.len() as f64)
+    }
+}
+
+/// Distance from a point to the segment `ab`
+fn distance_to_segment(point: Vec3, a: Vec3, b: Vec3) -> f64 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.dot(ab)).clamp(0.0, 1.0);
+    (point - (a + ab * t)).length()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picking::{pick, Ray};
+
+    #[test]
+    fn hit_selects_nearest_element_of_face() {
+        let cube = Mesh::cube();
+        // Hits face 0 (z = -1) close to its edge 1-2 and nearer to vertex 2 than to vertex 1
+        let ray = Ray::through(Vec3::new(0.9, 0.5, -5.0), Vec3::new(0.9, 0.5, 0.0));
+        let hit = pick(&ray, &cube, &cube.positions).unwrap();
+        let positions = &cube.positions;
+        assert_eq!(
+            SelectionMode::Face.element_at(&cube, positions, hit.face, hit.position),
+            0
+        );
+        let edge = SelectionMode::Edge.element_at(&cube, positions, hit.face, hit.position);
+        assert_eq!(cube.edges()[edge], (1, 2));
+        assert_eq!(
+            SelectionMode::Vertex.element_at(&cube, positions, hit.face, hit.position),
+            2
+        );
+
+        let (normal, position) = SelectionMode::Edge.describe(&cube, positions, edge);
+        assert!((normal - Vec3::new(1.0, 0.0, -1.0).normalize()).length() < 1e-9);
+        assert!((position - Vec3::new(1.0, 0.0, -1.0)).length() < 1e-9);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::background::BackgroundMode;
+use crate::camera::{CameraMode, Projection, MAX_FLY_SPEED, MIN_FLY_SPEED};
+use crate::color::{Palette, ToneMapping, MAX_EXPOSURE, MIN_EXPOSURE};
+use crate::fog::FogMode;
+use crate::gizmo::GizmoMode;
+use crate::math::Vec3;
+use crate::postprocess::PostEffect;
+use crate::rotation::RotationMode;
+use crate::selection::SelectionMode;
+use crate::ssao;
+use crate::state::{AppState, TransparencyMode};
+use crate::views::{Bookmark, BOOKMARK_SLOTS};
+use druid::Color;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Name of the settings file inside the configuration directory
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// Render settings persisted between sessions.
+///
+/// Missing entries fall back to the defaults of [`AppState`], so older files keep loading.
+#[derive(PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub projection: Projection,
+    pub camera_mode: CameraMode,
+    pub fly_speed: f64,
+    pub min_distance: f64,
+    pub max_distance: f64,
+    pub zoom_smoothing: f64,
+    pub rotation_mode: RotationMode,
+    pub orbit_damping: f64,
+    pub selection_mode: SelectionMode,
+    pub gizmo: GizmoMode,
+    pub angular_velocity: Vec3,
+    pub wireframe: bool,
+    pub light_intensity: f64,
+    pub palette: Palette,
+    #[serde(with = "hex_colors")]
+    pub face_colors: [Color; 6],
+    pub show_panel: bool,
+    pub transparency: TransparencyMode,
+    pub opacity: f64,
+    pub tone_mapping: ToneMapping,
+    pub exposure: f64,
+    #[serde(deserialize_with = "known_effects::deserialize")]
+    pub post_effects: Vec<PostEffect>,
+    pub ssao: bool,
+    pub ssao_radius: f64,
+    pub ssao_strength: f64,
+    pub background: BackgroundMode,
+    #[serde(with = "hex_color")]
+    pub background_color: Color,
+    #[serde(with = "hex_color")]
+    pub background_color_bottom: Color,
+    pub background_image: String,
+    pub fog: FogMode,
+    #[serde(with = "hex_color")]
+    pub fog_color: Color,
+    pub fog_start: f64,
+    pub fog_end: f64,
+    pub fog_density: f64,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::from_state(&AppState::default())
+    }
+}
+
+impl Settings {
+    /// Location of the settings file in the platform configuration directory
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join(SETTINGS_FILE))
+    }
+
+    /// Loads the saved settings, or the defaults if none have been saved yet
+    pub fn load() -> Result<Settings, Box<dyn Error>> {
+        let Some(path) = Settings::path() else {
+            return Ok(Settings::default());
+        };
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+        let contents = fs::read_to_string(&path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Writes the settings to the settings file, creating its directory if needed
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Settings::path().ok_or("no configuration directory available")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Captures the persisted subset of the application state
+    pub fn from_state(data: &AppState) -> Settings {
+        Settings {
+            projection: data.projection,
+            camera_mode: data.camera_mode,
+            fly_speed: data.fly_speed,
+            min_distance: data.min_distance,
+            max_distance: data.max_distance,
+            zoom_smoothing: data.zoom_smoothing,
+            rotation_mode: data.rotation_mode,
+            orbit_damping: data.orbit_damping,
+            selection_mode: data.selection_mode,
+            gizmo: data.gizmo,
+            angular_velocity: data.angular_velocity,
+            wireframe: data.wireframe,
+            light_intensity: data.light_intensity,
+            palette: data.palette,
+            face_colors: data.face_colors,
+            show_panel: data.show_panel,
+            transparency: data.transparency,
+            opacity: data.opacity,
+            tone_mapping: data.tone_mapping,
+            exposure: data.exposure,
+            post_effects: data.post_effects.to_vec(),
+            ssao: data.ssao,
+            ssao_radius: data.ssao_radius,
+            ssao_strength: data.ssao_strength,
+            background: data.background,
+            background_color: data.background_color,
+            background_color_bottom: data.background_color_bottom,
+            background_image: data.background_image.clone(),
+            fog: data.fog,
+            fog_color: data.fog_color,
+            fog_start: data.fog_start,
+            fog_end: data.fog_end,
+            fog_density: data.fog_density,
+            bookmarks: data.bookmarks.to_vec(),
+        }
+    }
+
+    /// Applies the settings to the application state
+    pub fn apply(&self, data: &mut AppState) {
+        data.projection = self.projection;
+        data.camera_mode = self.camera_mode;
+        data.fly_speed = self.fly_speed.clamp(MIN_FLY_SPEED, MAX_FLY_SPEED);
+        data.min_distance = self.min_distance.max(0.1);
+        data.max_distance = self.max_distance.max(data.min_distance);
+        data.zoom_smoothing = self.zoom_smoothing.max(0.0);
+        data.rotation_mode = self.rotation_mode;
+        data.orbit_damping = self.orbit_damping.max(0.0);
+        data.selection_mode = self.selection_mode;
+        data.gizmo = self.gizmo;
+        data.angular_velocity = self.angular_velocity;
+        data.scale_angular_velocity(1.0);
+        data.wireframe = self.wireframe;
+        data.light_intensity = self.light_intensity.max(0.0);
+        data.palette = self.palette;
+        data.face_colors = self.face_colors;
+        data.show_panel = self.show_panel;
+        data.transparency = self.transparency;
+        data.opacity = self.opacity.clamp(0.0, 1.0);
+        data.tone_mapping = self.tone_mapping;
+        data.exposure = self.exposure.clamp(MIN_EXPOSURE, MAX_EXPOSURE);
+        data.post_effects = Arc::new(self.post_effects.clone());
+        data.ssao = self.ssao;
+        data.ssao_radius = self.ssao_radius.clamp(ssao::MIN_RADIUS, ssao::MAX_RADIUS);
+        data.ssao_strength = self.ssao_strength.clamp(0.0, 1.0);
+        data.background = self.background;
+        data.background_color = self.background_color;
+        data.background_color_bottom = self.background_color_bottom;
+        data.background_image = self.background_image.clone();
+        data.fog = self.fog;
+        data.fog_color = self.fog_color;
+        data.fog_start = self.fog_start;
+        data.fog_end = self.fog_end.max(self.fog_start);
+        data.fog_density = self.fog_density.max(0.0);
+        data.bookmarks = Arc::new(Vec::new());
+        for &bookmark in &self.bookmarks {
+            if (1..=BOOKMARK_SLOTS).contains(&bookmark.slot) {
+                data.set_bookmark(bookmark);
+            }
+        }
+    }
+}
+
+/// Serializes colors as `#rrggbbaa` hex strings
+mod hex_color {
+    use druid::Color;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Color::from_hex_str(&hex).map_err(|err| D::Error::custom(format!("{:?}", err)))
+    }
+
+    pub fn to_hex(color: &Color) -> String {
+        let (r, g, b, a) = color.as_rgba8();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+/// Reads the post-processing chain, skipping effects that no longer exist
+mod known_effects {
+    use crate::postprocess::PostEffect;
+    use serde::de::value::{Error, StringDeserializer};
+    use serde::de::IntoDeserializer;
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PostEffect>, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        Ok(names
+            .into_iter()
+            .filter_map(|name| {
+                let name: StringDeserializer<Error> = name.into_deserializer();
+                PostEffect::deserialize(name).ok()
+            })
+            .collect())
+    }
+}
+
+/// Serializes fixed-size lists of colors as arrays of `#rrggbbaa` hex strings
+mod hex_colors {
+    use super::hex_color::to_hex;
+    use druid::Color;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        colors: &[Color; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        colors
+            .iter()
+            .map(to_hex)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[Color; N], D::Error> {
+        let colors = Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hex| {
+                Color::from_hex_str(hex).map_err(|err| D::Error::custom(format!("{:?}", err)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let count = colors.len();
+        colors
+            .try_into()
+            .map_err(|_| D::Error::custom(format!("expected {} colors, found {}", N, count)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quaternion::Quat;
+
+    /// Saves the state's settings as TOML and applies them to a fresh state
+    fn round_trip(data: &AppState) -> AppState {
+        let contents = toml::to_string_pretty(&Settings::from_state(data)).unwrap();
+        let settings: Settings = toml::from_str(&contents).unwrap();
+        let mut restored = AppState::default();
+        settings.apply(&mut restored);
+        restored
+    }
+
+    #[test]
+    fn settings_round_trip_through_toml() {
+        let mut data = AppState {
+            projection: Projection::Orthographic,
+            fly_speed: 7.5,
+            angular_velocity: Vec3::new(0.5, -1.0, 2.0),
+            light_intensity: 1.75,
+            exposure: 2.5,
+            ssao_radius: 0.3,
+            background_color_bottom: Color::rgba8(10, 20, 30, 40),
+            background_image: "/tmp/picture.png".into(),
+            fog_color: Color::rgb8(200, 100, 50),
+            fog_density: 0.25,
+            ..AppState::default()
+        };
+        data.set_palette(Palette::OkabeIto);
+        data.face_colors[3] = Color::rgba8(1, 2, 3, 255);
+        data.toggle_post_effect(PostEffect::Vignette);
+        data.toggle_post_effect(PostEffect::Bloom);
+        data.set_bookmark(Bookmark {
+            slot: 4,
+            camera: data.camera,
+            orientation: Quat::from_euler(Vec3::new(0.1, 0.2, 0.3)),
+            projection: Projection::Perspective,
+        });
+
+        let restored = round_trip(&data);
+        assert!(Settings::from_state(&restored) == Settings::from_state(&data));
+        assert_eq!(
+            restored.post_effects.as_slice(),
+            [PostEffect::Vignette, PostEffect::Bloom]
+        );
+    }
+
+    #[test]
+    fn every_fog_and_background_mode_round_trips() {
+        let mut data = AppState::default();
+        for _ in 0..3 {
+            data.fog = data.fog.next();
+            data.background = data.background.next();
+            let restored = round_trip(&data);
+            assert!(restored.fog == data.fog);
+            assert!(restored.background == data.background);
+        }
+    }
+
+    #[test]
+    fn removed_effects_are_skipped() {
+        let settings: Settings =
+            toml::from_str("post_effects = [\"depth_fog\", \"outline\"]").unwrap();
+        assert_eq!(settings.post_effects, [PostEffect::Outline]);
+    }
+
+    #[test]
+    fn missing_entries_keep_their_defaults() {
+        let settings: Settings = toml::from_str("fog = \"exponential\"").unwrap();
+        let mut data = AppState::default();
+        settings.apply(&mut data);
+        assert!(data.fog == FogMode::Exponential);
+        assert!(data.background == AppState::default().background);
+    }
+
+    #[test]
+    fn hand_edited_values_are_kept_in_range() {
+        let mut data = AppState::default();
+        for (entries, exposure) in [
+            ("exposure = 0.0", MIN_EXPOSURE),
+            ("exposure = 100.0", MAX_EXPOSURE),
+        ] {
+            let settings: Settings = toml::from_str(entries).unwrap();
+            settings.apply(&mut data);
+            assert_eq!(data.exposure, exposure);
+        }
+
+        let settings: Settings =
+            toml::from_str("exposure = -2.0\nfog_start = 6.0\nfog_end = 3.0\nfog_density = -1.0")
+                .unwrap();
+        settings.apply(&mut data);
+        assert_eq!(data.exposure, MIN_EXPOSURE);
+        assert_eq!((data.fog_start, data.fog_end), (6.0, 6.0));
+        assert_eq!(data.fog_density, 0.0);
+    }
+}
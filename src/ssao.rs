@@ -0,0 +1,254 @@
+use crate::graphics::{FrameBuffer, Viewport, NEAR_PLANE};
+use crate::math::{Vec2, Vec3};
+
+/// Number of hemisphere samples taken per pixel
+const SAMPLE_COUNT: usize = 12;
+/// Depth offset that keeps flat surfaces from occluding themselves
+const BIAS: f64 = 0.02;
+/// Radius of the blur applied to the raw occlusion, in pixels
+const BLUR_RADIUS: usize = 2;
+/// Smallest sampling radius in view-space units
+pub const MIN_RADIUS: f64 = 0.05;
+/// Largest sampling radius in view-space units
+pub const MAX_RADIUS: f64 = 5.0;
+
+/// Screen-space ambient occlusion parameters
+#[derive(Clone, Copy)]
+pub struct SsaoParams {
+    /// Sampling radius in view-space units
+    pub radius: f64,
+    /// How strongly occlusion darkens the ambient term (0.0 - 1.0)
+    pub strength: f64,
+}
+
+/// Darkens the ambient term of opaque surfaces in creases and contact areas.
+///
+/// Positions are reconstructed from the depth buffer and normals from the
+/// depth gradient. The occlusion is blurred to hide sampling noise and then
+/// multiplied into the ambient radiance stored in the frame buffer.
+pub fn apply_ssao(frame: &mut FrameBuffer, viewport: &Viewport, params: SsaoParams) {
+    let occlusion = compute_occlusion(frame, viewport, params);
+    let occlusion = blur(&occlusion, &frame.depth, frame.width, frame.height);
+
+    for (offset, ao) in occlusion.iter().enumerate() {
+        if !frame.depth[offset].is_finite() {
+            continue;
+        }
+        let ambient = frame.ambient[offset];
+        let color = &mut frame.color[offset];
+        for i in 0..3 {
+            color[i] -= ambient[i] * (1.0 - ao);
+        }
+    }
+}
+
+/// Reconstructs the view-space position of the surface at a pixel
+fn position_at(frame: &FrameBuffer, viewport: &Viewport, x: usize, y: usize) -> Option<Vec3> {
+    let depth = frame.depth[y * frame.width + x];
+    depth
+        .is_finite()
+        .then(|| viewport.unproject(Vec2::new(x as f64 + 0.5, y as f64 + 0.5), depth))
+}
+
+/// Reconstructs a view-facing surface normal from neighbouring depths
+fn normal_at(frame: &FrameBuffer, viewport: &Viewport, x: usize, y: usize) -> Option<Vec3> {
+    let center = position_at(frame, viewport, x, y)?;
+
+    // Use the neighbour closest in depth on each axis to avoid smearing across edges
+    let pick = |a: Option<Vec3>, b: Option<Vec3>| match (a, b) {
+        (Some(a), Some(b)) => {
+            let (da, db) = (a - center, center - b);
+            Some(if da.z.abs() < db.z.abs() { da } else { db })
+        }
+        (Some(a), None) => Some(a - center),
+        (None, Some(b)) => Some(center - b),
+        (None, None) => None,
+    };
+    let right = (x + 1 < frame.width)
+        .then(|| position_at(frame, viewport, x + 1, y))
+        .flatten();
+    let left = x
+        .checked_sub(1)
+        .and_then(|x| position_at(frame, viewport, x, y));
+    let down = (y + 1 < frame.height)
+        .then(|| position_at(frame, viewport, x, y + 1))
+        .flatten();
+    let up = y
+        .checked_sub(1)
+        .and_then(|y| position_at(frame, viewport, x, y));
+    let dx = pick(right, left)?;
+    let dy = pick(down, up)?;
+
+    let n = dx.cross(dy);
+    if n.length_squared() == 0.0 {
+        return None;
+    }
+    // The viewer looks down +z, so visible surfaces face -z
+    let n = n.normalize();
+    Some(if n.z > 0.0 { -n } else { n })
+}
+
+/// Fixed hemisphere kernel around +z, denser towards the center
+fn kernel() -> [Vec3; SAMPLE_COUNT] {
+    let mut samples = [Vec3::ZERO; SAMPLE_COUNT];
+    for (i, sample) in samples.iter_mut().enumerate() {
+        let t = (i as f64 + 0.5) / SAMPLE_COUNT as f64;
+        // Golden-angle spiral over the hemisphere
+        let theta = i as f64 * 2.399_963;
+        let z = 1.0 - t;
+        let r = (1.0 - z * z).sqrt();
+        let scale = 0.1 + 0.9 * t * t;
+        *sample = Vec3::new(r * theta.cos(), r * theta.sin(), z) * scale;
+    }
+    samples
+}
+
+/// Computes the raw, unblurred ambient visibility (1.0 = unoccluded) per pixel
+fn compute_occlusion(frame: &FrameBuffer, viewport: &Viewport, params: SsaoParams) -> Vec<f64> {
+    let kernel = kernel();
+    let mut visibility = vec![1.0; frame.width * frame.height];
+
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let (Some(position), Some(normal)) = (
+                position_at(frame, viewport, x, y),
+                normal_at(frame, viewport, x, y),
+            ) else {
+                continue;
+            };
+
+            // Build a basis around the normal, rotated per pixel to trade banding for noise
+            let angle = ((x * 7 + y * 13) % 16) as f64 * std::f64::consts::TAU / 16.0;
+            let helper = if normal.x.abs() < 0.9 {
+                Vec3::X
+            } else {
+                Vec3::Y
+            };
+            let tangent = (helper - normal * helper.dot(normal)).normalize();
+            let bitangent = normal.cross(tangent);
+            let (sin, cos) = angle.sin_cos();
+
+            let mut occluded = 0.0;
+            for sample in &kernel {
+                let sx = sample.x * cos - sample.y * sin;
+                let sy = sample.x * sin + sample.y * cos;
+                let offset = tangent * sx + bitangent * sy + normal * sample.z;
+                let point = position + offset * params.radius;
+                // The kernel leans towards the camera, so samples near it can end up behind it
+                if point.z < NEAR_PLANE {
+                    continue;
+                }
+
+                let screen = viewport.project(point);
+                if screen.x < 0.0 || screen.y < 0.0 {
+                    continue;
+                }
+                let (sx, sy) = (screen.x as usize, screen.y as usize);
+                if sx >= frame.width || sy >= frame.height {
+                    continue;
+                }
+                let scene_depth = frame.depth[sy * frame.width + sx];
+                if scene_depth < point.z - BIAS {
+                    // Fade out occluders far outside the sampling radius
+                    let range = params.radius / (position.z - scene_depth).abs().max(1e-6);
+                    occluded += range.min(1.0);
+                }
+            }
+
+            let ao = 1.0 - params.strength * occluded / SAMPLE_COUNT as f64;
+            visibility[y * frame.width + x] = ao.clamp(0.0, 1.0);
+        }
+    }
+    visibility
+}
+
+/// Box blur that only averages pixels covered by geometry
+fn blur(occlusion: &[f64], depth: &[f64], width: usize, height: usize) -> Vec<f64> {
+    let mut blurred = occlusion.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            if !depth[y * width + x].is_finite() {
+                continue;
+            }
+            let (mut sum, mut count) = (0.0, 0.0);
+            for sy in y.saturating_sub(BLUR_RADIUS)..=(y + BLUR_RADIUS).min(height - 1) {
+                for sx in x.saturating_sub(BLUR_RADIUS)..=(x + BLUR_RADIUS).min(width - 1) {
+                    let offset = sy * width + sx;
+                    if depth[offset].is_finite() {
+                        sum += occlusion[offset];
+                        count += 1.0;
+                    }
+                }
+            }
+            blurred[y * width + x] = sum / count;
+        }
+    }
+    blurred
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Projection;
+
+    const SIZE: usize = 40;
+    const PARAMS: SsaoParams = SsaoParams {
+        radius: 0.5,
+        strength: 1.0,
+    };
+
+    /// Lit frame whose surface depth at each pixel is given in view space
+    fn frame(depth: impl Fn(f64, f64) -> f64, viewport: &Viewport) -> FrameBuffer {
+        let mut frame = FrameBuffer::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let offset = y * SIZE + x;
+                let view = viewport.unproject(Vec2::new(x as f64 + 0.5, y as f64 + 0.5), 1.0);
+                frame.depth[offset] = depth(view.x, view.y);
+                frame.set_pixel(offset, [1.0, 1.0, 1.0, 1.0]);
+                frame.ambient[offset] = [0.2; 3];
+            }
+        }
+        frame
+    }
+
+    fn viewport() -> Viewport {
+        let center = Vec2::new(SIZE as f64 / 2.0, SIZE as f64 / 2.0);
+        Viewport::new(center, 10.0, Projection::Orthographic)
+    }
+
+    #[test]
+    fn flat_surfaces_are_not_occluded() {
+        let viewport = viewport();
+        let mut frame = frame(|_, _| 3.0, &viewport);
+        apply_ssao(&mut frame, &viewport, PARAMS);
+        assert!(frame.color.iter().all(|color| color[0] == 1.0));
+    }
+
+    #[test]
+    fn creases_darken_the_ambient_term() {
+        let viewport = viewport();
+        // A valley running down the middle, with walls rising towards the viewer
+        let mut frame = frame(|x, _| 3.0 - x.abs(), &viewport);
+        apply_ssao(&mut frame, &viewport, PARAMS);
+        let at = |x: usize| frame.color[SIZE / 2 * SIZE + x][0];
+        assert!(at(SIZE / 2) < 1.0, "crease {}", at(SIZE / 2));
+        // Occlusion only removes ambient light, never the direct lighting
+        assert!(at(SIZE / 2) >= 0.8);
+        assert!(at(SIZE / 2) < at(SIZE - 3));
+    }
+
+    #[test]
+    fn samples_behind_the_camera_are_skipped() {
+        let center = Vec2::new(SIZE as f64 / 2.0, SIZE as f64 / 2.0);
+        let viewport = Viewport::new(center, 10.0, Projection::Perspective);
+        // Most of the kernel reaches past the near plane and behind the viewer
+        let mut frame = frame(|_, _| NEAR_PLANE + 0.01, &viewport);
+        let params = SsaoParams {
+            radius: MAX_RADIUS,
+            strength: 1.0,
+        };
+        apply_ssao(&mut frame, &viewport, params);
+        assert!(frame.color.iter().all(|color| color[0] == 1.0));
+    }
+}
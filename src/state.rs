@@ -1,22 +1,335 @@
-use druid::Data;
+use crate::background::BackgroundMode;
+use crate::camera::{Camera, CameraMode, Projection, MAX_DISTANCE, MIN_DISTANCE};
+use crate::color::{Palette, ToneMapping};
+use crate::fog::FogMode;
+use crate::gizmo::GizmoMode;
+use crate::math::{Mat4, Vec3};
+use crate::postprocess::PostEffect;
+use crate::quaternion::Quat;
+use crate::rotation::RotationMode;
+use crate::selection::SelectionMode;
+use crate::views::Bookmark;
+use druid::{Color, Data, Lens};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Smallest uniform scale of the cube
+pub const MIN_SCALE: f64 = 0.05;
+/// Largest uniform scale of the cube
+pub const MAX_SCALE: f64 = 20.0;
+/// Fastest auto-rotation speed in radians per second
+pub const MAX_ANGULAR_SPEED: f64 = 30.0;
+
+/// How faces are composited when rendering
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransparencyMode {
+    /// Faces are drawn fully opaque with depth testing
+    Opaque,
+    /// Triangles are sorted back to front and alpha blended
+    Sorted,
+    /// Per-pixel fragment lists resolved in depth order (order-independent)
+    ABuffer,
+}
+
+impl TransparencyMode {
+    /// Returns the next mode in the cycle
+    pub fn next(self) -> Self {
+        match self {
+            TransparencyMode::Opaque => TransparencyMode::Sorted,
+            TransparencyMode::Sorted => TransparencyMode::ABuffer,
+            TransparencyMode::ABuffer => TransparencyMode::Opaque,
+        }
+    }
+
+    /// Human-readable name of the mode
+    pub fn name(self) -> &'static str {
+        match self {
+            TransparencyMode::Opaque => "Opaque",
+            TransparencyMode::Sorted => "Sorted",
+            TransparencyMode::ABuffer => "A-buffer",
+        }
+    }
+}
 
 /// Application state
-#[derive(Clone, Data)]
+#[derive(Clone, Data, Lens)]
 pub struct AppState {
-    /// Current rotation angle around the X-axis
-    pub angle_x: f64,
-    /// Current rotation angle around the Y-axis
-    pub angle_y: f64,
-    /// Translation vector (x, y)
-    pub translation: [f64; 2],
+    /// Current orientation of the cube
+    pub orientation: Quat,
+    /// Position of the cube's center in world space
+    pub position: Vec3,
+    /// Uniform scale of the cube
+    pub scale: f64,
+    /// Manipulator shown over the cube
+    pub gizmo: GizmoMode,
+    /// Viewer orbiting the scene
+    pub camera: Camera,
+    /// Perspective or orthographic projection
+    pub projection: Projection,
+    /// Orbit or fly camera controls
+    pub camera_mode: CameraMode,
+    /// Fly camera speed in world units per second
+    pub fly_speed: f64,
+    /// Closest the orbit camera may zoom towards its target
+    pub min_distance: f64,
+    /// Farthest the orbit camera may zoom away from its target
+    pub max_distance: f64,
+    /// Rate of the exponential zoom smoothing per second (0.0 zooms instantly)
+    pub zoom_smoothing: f64,
+    /// Kind of mesh element that clicking selects
+    pub selection_mode: SelectionMode,
+    /// Indices of the selected elements of the current kind, in selection order
+    pub selection: Arc<Vec<usize>>,
+    /// Saved views, at most one per slot
+    pub bookmarks: Arc<Vec<Bookmark>>,
+    /// How mouse drags orbit the camera
+    pub rotation_mode: RotationMode,
+    /// Rate per second at which the orbit slows down after a drag is released
+    pub orbit_damping: f64,
+    /// Auto-rotation speed around the X, Y and Z axes in radians per second
+    pub angular_velocity: Vec3,
     /// Enable debug mode
     pub debug: bool,
+    /// Show the face ID buffer used for picking instead of the shaded image
+    pub show_ids: bool,
     /// Simulation paused
     pub paused: bool,
     /// Wireframe mode enabled
     pub wireframe: bool,
-    /// Zoom level
-    pub zoom: f64,
     /// Light position in world space
-    pub light_position: [f64; 3],
+    pub light_position: Vec3,
+    /// Draw the light as a gizmo that can be dragged
+    pub show_light: bool,
+    /// Brightness of the light, scaling its diffuse contribution
+    pub light_intensity: f64,
+    /// Palette the face colors were last reset to
+    pub palette: Palette,
+    /// Base colors of the faces, repeated if the mesh has more faces
+    pub face_colors: [Color; 6],
+    /// Show the settings side panel
+    pub show_panel: bool,
+    /// Face compositing mode
+    pub transparency: TransparencyMode,
+    /// Face opacity used by the translucent modes (0.0 - 1.0)
+    pub opacity: f64,
+    /// Tone mapping operator applied to the HDR frame
+    pub tone_mapping: ToneMapping,
+    /// Exposure multiplier applied before tone mapping
+    pub exposure: f64,
+    /// Post-processing effects, applied in order
+    pub post_effects: Arc<Vec<PostEffect>>,
+    /// Screen-space ambient occlusion enabled
+    pub ssao: bool,
+    /// Ambient occlusion sampling radius in view-space units
+    pub ssao_radius: f64,
+    /// Ambient occlusion strength (0.0 - 1.0)
+    pub ssao_strength: f64,
+    /// Background drawn behind the scene
+    pub background: BackgroundMode,
+    /// Solid background color, also the top of the gradient
+    pub background_color: Color,
+    /// Bottom color of the gradient background
+    pub background_color_bottom: Color,
+    /// Path of the background image
+    pub background_image: String,
+    /// Depth fog mode
+    pub fog: FogMode,
+    /// Fog color
+    pub fog_color: Color,
+    /// Depth at which fog begins
+    pub fog_start: f64,
+    /// Depth at which linear fog becomes opaque
+    pub fog_end: f64,
+    /// Density of exponential fog
+    pub fog_density: f64,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        AppState {
+            orientation: Quat::IDENTITY,
+            position: Vec3::ZERO,
+            scale: 1.0,
+            gizmo: GizmoMode::Off,
+            rotation_mode: RotationMode::Arcball,
+            orbit_damping: 4.0,
+            camera: Camera::default(),
+            projection: Projection::Perspective,
+            camera_mode: CameraMode::Orbit,
+            fly_speed: 2.0,
+            min_distance: MIN_DISTANCE,
+            max_distance: MAX_DISTANCE,
+            zoom_smoothing: 12.0,
+            selection_mode: SelectionMode::Face,
+            selection: Arc::new(Vec::new()),
+            bookmarks: Arc::new(Vec::new()),
+            angular_velocity: Vec3::new(0.625, 1.25, 0.0),
+            debug: false,
+            show_ids: false,
+            paused: false,
+            wireframe: false,
+            light_position: Vec3::new(2.0, 2.0, -5.0),
+            show_light: true,
+            light_intensity: 1.0,
+            palette: Palette::Default,
+            face_colors: Palette::Default.colors(),
+            show_panel: false,
+            transparency: TransparencyMode::Opaque,
+            opacity: 0.5,
+            tone_mapping: ToneMapping::Aces,
+            exposure: 1.0,
+            post_effects: Arc::new(Vec::new()),
+            ssao: false,
+            ssao_radius: 0.5,
+            ssao_strength: 1.0,
+            background: BackgroundMode::Solid,
+            background_color: Color::BLACK,
+            background_color_bottom: Color::rgb8(60, 70, 90),
+            background_image: String::new(),
+            fog: FogMode::Off,
+            fog_color: Color::rgb8(128, 140, 160),
+            fog_start: 5.0,
+            fog_end: 7.5,
+            fog_density: 0.8,
+        }
+    }
+}
+
+impl AppState {
+    /// Switches to a palette, replacing any edited face colors
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.face_colors = palette.colors();
+    }
+
+    /// Enables a post-processing effect at the end of the chain, or removes it if already enabled
+    pub fn toggle_post_effect(&mut self, effect: PostEffect) {
+        let effects = Arc::make_mut(&mut self.post_effects);
+        if let Some(index) = effects.iter().position(|&e| e == effect) {
+            effects.remove(index);
+        } else {
+            effects.push(effect);
+        }
+    }
+
+    /// Moves an enabled post-processing effect one step earlier in the chain.
+    ///
+    /// The first effect moves to the end instead, so repeated moves reach every order.
+    pub fn move_post_effect_earlier(&mut self, effect: PostEffect) {
+        let effects = Arc::make_mut(&mut self.post_effects);
+        match effects.iter().position(|&e| e == effect) {
+            Some(0) => effects.rotate_left(1),
+            Some(index) => effects.swap(index - 1, index),
+            None => {}
+        }
+    }
+
+    /// Selects a clicked element, or clears the selection if nothing was clicked.
+    ///
+    /// With `extend` the element is toggled in the selection instead and a click on
+    /// nothing keeps the selection.
+    pub fn select(&mut self, element: Option<usize>, extend: bool) {
+        let selection = Arc::make_mut(&mut self.selection);
+        match (element, extend) {
+            (Some(element), true) => {
+                if let Some(index) = selection.iter().position(|&e| e == element) {
+                    selection.remove(index);
+                } else {
+                    selection.push(element);
+                }
+            }
+            (Some(element), false) => *selection = vec![element],
+            (None, true) => {}
+            (None, false) => selection.clear(),
+        }
+    }
+
+    /// Saves a bookmark, replacing any earlier one in the same slot
+    pub fn set_bookmark(&mut self, bookmark: Bookmark) {
+        let bookmarks = Arc::make_mut(&mut self.bookmarks);
+        bookmarks.retain(|b| b.slot != bookmark.slot);
+        bookmarks.push(bookmark);
+        bookmarks.sort_by_key(|b| b.slot);
+    }
+
+    /// Returns the bookmark saved in a slot, if any
+    pub fn bookmark(&self, slot: u8) -> Option<Bookmark> {
+        self.bookmarks.iter().find(|b| b.slot == slot).copied()
+    }
+
+    /// Scales the auto-rotation speed, keeping its axis
+    pub fn scale_angular_velocity(&mut self, factor: f64) {
+        self.set_angular_velocity(self.angular_velocity * factor);
+    }
+
+    /// Adds `step` radians per second to the auto-rotation around one axis (0 to 2).
+    ///
+    /// Unlike scaling, this starts a stopped axis and can reverse one by passing zero.
+    pub fn step_angular_velocity(&mut self, axis: usize, step: f64) {
+        let direction = [Vec3::X, Vec3::Y, Vec3::Z][axis];
+        self.set_angular_velocity(self.angular_velocity + direction * step);
+    }
+
+    /// Sets the auto-rotation, slowing it down to [`MAX_ANGULAR_SPEED`] if needed
+    fn set_angular_velocity(&mut self, velocity: Vec3) {
+        let speed = velocity.length();
+        self.angular_velocity = if speed > MAX_ANGULAR_SPEED {
+            velocity * (MAX_ANGULAR_SPEED / speed)
+        } else {
+            velocity
+        };
+    }
+
+    /// Transform from the cube's model space to world space
+    pub fn model_matrix(&self) -> Mat4 {
+        Mat4::translation(self.position)
+            * self.orientation.to_mat4()
+            * Mat4::scale(Vec3::new(self.scale, self.scale, self.scale))
+    }
+
+    /// Maps a point from the cube's model space to world space
+    pub fn model_to_world(&self, point: Vec3) -> Vec3 {
+        self.position + self.orientation.rotate(point * self.scale)
+    }
+
+    /// Applies a rotation in world space on top of the current orientation
+    pub fn rotate(&mut self, rotation: Quat) {
+        self.orientation = (rotation * self.orientation).normalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaling_keeps_the_axis_and_clamps_the_speed() {
+        let mut data = AppState {
+            angular_velocity: Vec3::new(0.0, 3.0, 4.0),
+            ..AppState::default()
+        };
+        data.scale_angular_velocity(2.0);
+        assert_eq!(data.angular_velocity, Vec3::new(0.0, 6.0, 8.0));
+        data.scale_angular_velocity(100.0);
+        assert!((data.angular_velocity.length() - MAX_ANGULAR_SPEED).abs() < 1e-9);
+        assert!((data.angular_velocity.normalize() - Vec3::new(0.0, 0.6, 0.8)).length() < 1e-9);
+        data.scale_angular_velocity(0.0);
+        assert_eq!(data.angular_velocity, Vec3::ZERO);
+    }
+
+    #[test]
+    fn steps_start_and_reverse_one_axis() {
+        let mut data = AppState {
+            angular_velocity: Vec3::ZERO,
+            ..AppState::default()
+        };
+        data.step_angular_velocity(0, 0.5);
+        assert_eq!(data.angular_velocity, Vec3::new(0.5, 0.0, 0.0));
+        data.step_angular_velocity(0, -1.0);
+        data.step_angular_velocity(2, 0.25);
+        assert_eq!(data.angular_velocity, Vec3::new(-0.5, 0.0, 0.25));
+        data.step_angular_velocity(1, 1000.0);
+        assert!((data.angular_velocity.length() - MAX_ANGULAR_SPEED).abs() < 1e-9);
+    }
 }
@@ -1,6 +1,11 @@
+use crate::math::{Vec2, Vec3};
+
 /// Vertex structure with position, screen position, and normal
+#[derive(Clone, Copy)]
 pub struct Vertex {
-    pub position: [f64; 3],
-    pub screen_position: [f64; 2],
-    pub normal: [f64; 3],
+    pub position: Vec3,
+    pub screen_position: Vec2,
+    pub normal: Vec3,
+    /// Homogeneous w after projection, used for perspective-correct interpolation
+    pub w: f64,
 }
@@ -0,0 +1,113 @@
+use crate::camera::{Camera, Projection};
+use crate::math::Vec3;
+use crate::quaternion::Quat;
+use druid::Data;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+/// Number of bookmark slots, bound to the digit keys 1-9
+pub const BOOKMARK_SLOTS: u8 = 9;
+
+/// Predefined viewing directions around the camera target
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StandardView {
+    Front,
+    Back,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    Isometric,
+}
+
+impl StandardView {
+    pub const ALL: [StandardView; 7] = [
+        StandardView::Front,
+        StandardView::Back,
+        StandardView::Top,
+        StandardView::Bottom,
+        StandardView::Left,
+        StandardView::Right,
+        StandardView::Isometric,
+    ];
+
+    /// Human-readable name of the view
+    pub fn name(self) -> &'static str {
+        match self {
+            StandardView::Front => "Front",
+            StandardView::Back => "Back",
+            StandardView::Top => "Top",
+            StandardView::Bottom => "Bottom",
+            StandardView::Left => "Left",
+            StandardView::Right => "Right",
+            StandardView::Isometric => "Isometric",
+        }
+    }
+
+    /// Camera orientation of the view, as used by [`Camera::from_orientation`].
+    ///
+    /// The front view looks down +z from the default camera position; top and
+    /// bottom keep the front of the scene at the bottom and top of the screen.
+    pub fn orientation(self) -> Quat {
+        let yaw = |angle| Quat::from_axis_angle(Vec3::Y, angle);
+        let pitch = |angle| Quat::from_axis_angle(Vec3::X, angle);
+        match self {
+            StandardView::Front => Quat::IDENTITY,
+            StandardView::Back => yaw(PI),
+            StandardView::Top => pitch(-FRAC_PI_2),
+            StandardView::Bottom => pitch(FRAC_PI_2),
+            StandardView::Left => yaw(FRAC_PI_2),
+            StandardView::Right => yaw(-FRAC_PI_2),
+            // Looks down the diagonal from the front, right and top
+            StandardView::Isometric => yaw(-FRAC_PI_4) * pitch(-(0.5f64).sqrt().atan()),
+        }
+    }
+
+    /// Camera looking at the same target from the same distance along this view
+    pub fn camera(self, current: &Camera) -> Camera {
+        Camera::from_orientation(current.target, current.distance(), self.orientation())
+    }
+}
+
+/// Saved view that can be restored later
+#[derive(Clone, Copy, Data, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// Slot number from 1 to [`BOOKMARK_SLOTS`]
+    pub slot: u8,
+    pub camera: Camera,
+    /// Orientation of the object
+    pub orientation: Quat,
+    pub projection: Projection,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::WORLD_UP;
+
+    fn assert_vec_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn standard_views_look_along_axes() {
+        let camera = Camera::default();
+        let offset = |view: StandardView| {
+            let camera = view.camera(&camera);
+            (camera.position - camera.target) / camera.distance()
+        };
+        assert_vec_eq(offset(StandardView::Front), Vec3::new(0.0, 0.0, -1.0));
+        assert_vec_eq(offset(StandardView::Back), Vec3::new(0.0, 0.0, 1.0));
+        assert_vec_eq(offset(StandardView::Top), WORLD_UP);
+        assert_vec_eq(offset(StandardView::Bottom), -WORLD_UP);
+        assert_vec_eq(offset(StandardView::Left), Vec3::new(-1.0, 0.0, 0.0));
+        assert_vec_eq(offset(StandardView::Right), Vec3::new(1.0, 0.0, 0.0));
+        assert_vec_eq(
+            offset(StandardView::Isometric),
+            Vec3::new(1.0, -1.0, -1.0).normalize(),
+        );
+        // The isometric view keeps the horizon level
+        let isometric = StandardView::Isometric.camera(&camera);
+        assert!(isometric.right().dot(WORLD_UP).abs() < 1e-9);
+    }
+}
@@ -1,8 +1,32 @@
-use crate::graphics::{draw_line, draw_triangle};
-use crate::math::{calculate_normal, multiply_matrices, multiply_matrix_vector, point_in_triangle};
-use crate::state::AppState;
+use crate::abuffer::ABuffer;
+use crate::background::{fill_background, BackgroundImage};
+use crate::camera::{
+    Camera, CameraMode, CameraTransition, Projection, FIELD_OF_VIEW, MAX_FLY_SPEED, MIN_FLY_SPEED,
+    WORLD_UP,
+};
+use crate::color::{to_linear, MAX_EXPOSURE, MIN_EXPOSURE};
+use crate::fog::Fog;
+use crate::gizmo::{orbit_light, Gizmo, GizmoEdit, GizmoMode, Handle, GIZMO_SIZE, LIGHT_SIZE};
+use crate::graphics::{
+    clip_line_near, draw_line, draw_triangle, draw_triangle_blended, front_facing, overlay_mask,
+    FrameBuffer, Shading, Viewport, NEAR_PLANE,
+};
+use crate::history::History;
+use crate::idbuffer::IdBuffer;
+use crate::keymap::{Action, Keymap};
+use crate::math::{calculate_normal, Vec2, Vec3};
+use crate::mesh::{BoundingSphere, Mesh};
+use crate::panel::{EDIT_FINISHED, EDIT_STARTED};
+use crate::picking::{pick, Ray};
+use crate::postprocess::apply_effects;
+use crate::quaternion::Quat;
+use crate::selection::SelectionMode;
+use crate::settings::Settings;
+use crate::ssao::{self, apply_ssao, SsaoParams};
+use crate::state::{AppState, TransparencyMode, MAX_SCALE, MIN_SCALE};
 use crate::vertex::Vertex;
-use druid::kurbo::Point;
+use crate::views::Bookmark;
+use druid::kurbo::{BezPath, Circle, Line, Point};
 use druid::text::FontFamily;
 use druid::widget::prelude::*;
 use druid::widget::{Controller, ControllerHost, Label};
@@ -12,121 +36,859 @@ use druid::{
     piet::{InterpolationMode, Text, TextLayout, TextLayoutBuilder},
     Color, RenderContext, Widget, WindowDesc,
 };
+use std::collections::HashSet;
+use std::sync::Arc;
 use std::time::Instant;
 
+/// Radians the fly camera turns per pixel of mouse movement
+const LOOK_SENSITIVITY: f64 = 0.005;
+/// Natural log of the zoom factor per unit of mouse wheel movement
+const ZOOM_SPEED: f64 = 0.001;
+/// Natural log of the fly speed factor per unit of mouse wheel movement
+const FLY_WHEEL_SPEED: f64 = 0.001;
+/// Radians per second the view turns while an orbit key is held
+const KEY_ORBIT_SPEED: f64 = 1.5;
+/// Camera distances per second the view moves while a pan key is held
+const KEY_PAN_SPEED: f64 = 0.75;
+/// Natural log of the zoom factor per press of a zoom key
+const KEY_ZOOM_STEP: f64 = 0.25;
+/// Radians per second one press adds to or takes from the rotation around an axis
+const KEY_SPIN_STEP: f64 = 0.25;
+/// Seconds the mouse may rest before release without cancelling the orbit momentum
+const RELEASE_TIMEOUT: f64 = 0.05;
+/// Orbit speed in radians per second below which coasting stops
+const MIN_COAST_SPEED: f64 = 0.01;
+/// Pixels the mouse may move between press and release for a click
+const CLICK_TOLERANCE: f64 = 3.0;
+/// Seconds settings must stay unchanged before they are written to disk
+const SETTINGS_SAVE_DELAY: f64 = 1.0;
+/// Outline color of the element under the cursor
+const HOVER_COLOR: Color = Color::rgba8(255, 255, 255, 160);
+/// Outline color of selected elements
+const SELECTION_COLOR: Color = Color::rgb8(255, 160, 0);
+/// Pixels around a vertex in which face borders outline it
+const VERTEX_OUTLINE_RADIUS: f64 = 6.0;
+/// Color of the light gizmo
+const LIGHT_COLOR: Color = Color::rgb8(255, 220, 120);
+/// Distance of the debug overlay from the top left corner in pixels
+const DEBUG_MARGIN: f64 = 10.0;
+/// Height of a debug overlay line in pixels
+const DEBUG_LINE_HEIGHT: f64 = 20.0;
+
 /// 3D cube widget
 pub struct CubeWidget {
     frames_since_last_update: usize,
     last_fps_calculation: Instant,
     fps: f64,
-    /// Is the user currently dragging for rotation?
-    dragging_rotation: bool,
-    /// Is the user currently dragging for translation?
-    dragging_translation: bool,
+    /// Is the user currently dragging to orbit the camera?
+    dragging_orbit: bool,
+    /// Is the user currently dragging to pan the camera?
+    dragging_pan: bool,
+    /// Is the user currently dragging the light across the screen?
+    dragging_light: bool,
+    /// Is the user currently Alt-dragging to orbit the light?
+    orbiting_light: bool,
+    /// Is the user currently changing a setting in the panel?
+    panel_active: bool,
     /// Last mouse position
     last_mouse_pos: Point,
     /// Widget size
     size: Size,
+    /// Path and decoded contents of the last loaded background image
+    background_image: Option<(String, Option<BackgroundImage>)>,
+    /// Key bindings of the actions
+    keymap: Keymap,
+    /// Held actions whose keys are currently down
+    held_actions: HashSet<Action>,
+    /// Time of the last animation tick
+    last_tick: Instant,
+    /// World-space point the camera zooms towards
+    zoom_anchor: Vec3,
+    /// Natural log of the zoom factor still to be applied by the smoothing
+    pending_zoom: f64,
+    /// Animated camera move in progress, if any
+    camera_transition: Option<CameraTransition>,
+    /// View-space rotation vector of the orbit in radians per second, kept after a drag
+    orbit_velocity: Vec3,
+    /// Time of the last orbit drag movement
+    last_orbit_move: Instant,
+    /// Where the last mouse button was pressed, to tell clicks from drags
+    press_pos: Point,
+    /// Gizmo handle being dragged, if any
+    gizmo_drag: Option<Handle>,
+    /// Undo and redo stacks
+    history: History,
+    /// Settings as last saved or loaded
+    saved_settings: Option<Settings>,
+    /// Changed settings waiting to stay unchanged for a while before they are saved
+    pending_settings: Option<Settings>,
+    /// Time the settings were last compared with the saved ones
+    last_settings_check: Instant,
+    /// Current mouse position, if the cursor is over the widget
+    cursor_pos: Option<Point>,
+    /// Face IDs of the last frame, written by the main pass and taken from `frame.ids`
+    id_buffer: Option<IdBuffer>,
+    /// Mesh being displayed
+    mesh: Mesh,
 }
 
 impl CubeWidget {
-    pub fn new() -> Self {
+    pub fn new(keymap: Keymap) -> Self {
         CubeWidget {
             frames_since_last_update: 0,
             last_fps_calculation: Instant::now(),
             fps: 0.0,
-            dragging_rotation: false,
-            dragging_translation: false,
+            dragging_orbit: false,
+            dragging_pan: false,
+            dragging_light: false,
+            orbiting_light: false,
+            panel_active: false,
             last_mouse_pos: Point::ZERO,
             size: Size::ZERO,
+            background_image: None,
+            keymap,
+            held_actions: HashSet::new(),
+            last_tick: Instant::now(),
+            zoom_anchor: Vec3::ZERO,
+            pending_zoom: 0.0,
+            camera_transition: None,
+            orbit_velocity: Vec3::ZERO,
+            last_orbit_move: Instant::now(),
+            press_pos: Point::ZERO,
+            gizmo_drag: None,
+            history: History::default(),
+            saved_settings: None,
+            pending_settings: None,
+            last_settings_check: Instant::now(),
+            cursor_pos: None,
+            id_buffer: None,
+            mesh: Mesh::cube(),
         }
     }
 
-    /// Computes the projected vertices for the current state
-    fn compute_projected_vertices(&self, data: &AppState) -> Vec<Vertex> {
-        let center = Point::new(self.size.width / 2.0, self.size.height / 2.0);
-        let scale = (self.size.height.min(self.size.width) / 4.0) * data.zoom; // Adjusted scale
-
-        // Define cube vertices
-        let vertices = [
-            (-1.0, -1.0, -1.0), // 0
-            (1.0, -1.0, -1.0),  // 1
-            (1.0, 1.0, -1.0),   // 2
-            (-1.0, 1.0, -1.0),  // 3
-            (-1.0, -1.0, 1.0),  // 4
-            (1.0, -1.0, 1.0),   // 5
-            (1.0, 1.0, 1.0),    // 6
-            (-1.0, 1.0, 1.0),   // 7
-        ];
+    /// Returns the background image at `path`, loading it if the path changed
+    fn load_background_image(&mut self, path: &str) -> Option<&BackgroundImage> {
+        let cached = matches!(&self.background_image, Some((cached, _)) if cached == path);
+        if !cached {
+            let image = if path.is_empty() {
+                None
+            } else {
+                match BackgroundImage::load(path) {
+                    Ok(image) => Some(image),
+                    Err(err) => {
+                        eprintln!("Failed to load background image {}: {}", path, err);
+                        None
+                    }
+                }
+            };
+            self.background_image = Some((path.to_string(), image));
+        }
+        self.background_image
+            .as_ref()
+            .and_then(|(_, image)| image.as_ref())
+    }
+
+    /// Moves the fly camera according to the held movement keys
+    fn fly(&self, data: &mut AppState, dt: f64) {
+        let camera = &data.camera;
+        let mut direction = Vec3::ZERO;
+        for action in &self.held_actions {
+            direction += match action {
+                Action::MoveForward => camera.forward(),
+                Action::MoveBack => -camera.forward(),
+                Action::MoveRight => camera.right(),
+                Action::MoveLeft => -camera.right(),
+                Action::MoveUp => WORLD_UP,
+                Action::MoveDown => -WORLD_UP,
+                _ => Vec3::ZERO,
+            };
+        }
+        if direction.length_squared() > 0.0 {
+            data.camera
+                .translate(direction.normalize() * data.fly_speed * dt);
+        }
+    }
+
+    /// Rotates and pans the view according to the held orbit and pan keys.
+    ///
+    /// Returns whether any such key is held.
+    fn steer(&mut self, data: &mut AppState, dt: f64) -> bool {
+        let mut orbit = Vec2::ZERO;
+        let mut pan = Vec2::ZERO;
+        for action in &self.held_actions {
+            match action {
+                Action::OrbitLeft => orbit.x -= 1.0,
+                Action::OrbitRight => orbit.x += 1.0,
+                Action::OrbitUp => orbit.y -= 1.0,
+                Action::OrbitDown => orbit.y += 1.0,
+                Action::PanLeft => pan.x -= 1.0,
+                Action::PanRight => pan.x += 1.0,
+                Action::PanUp => pan.y -= 1.0,
+                Action::PanDown => pan.y += 1.0,
+                _ => {}
+            }
+        }
+        if orbit == Vec2::ZERO && pan == Vec2::ZERO {
+            return false;
+        }
+        // Keys take over the camera like a drag does
+        self.camera_transition = None;
+        self.orbit_velocity = Vec3::ZERO;
+        let orbit = orbit * (KEY_ORBIT_SPEED * dt);
+        match data.camera_mode {
+            CameraMode::Fly => data.camera.look(orbit.x, -orbit.y),
+            CameraMode::Orbit => {
+                // Turn the scene the way a turntable drag in the same direction would
+                let up = data.camera.view_matrix().transform_vector(WORLD_UP);
+                let spin = Quat::from_axis_angle(up, orbit.x);
+                let tilt = Quat::from_axis_angle(Vec3::X, orbit.y);
+                data.camera.orbit(tilt * spin);
+            }
+        }
+        // Move the scene with the keys, as dragging with the right button would
+        let pan = pan * (KEY_PAN_SPEED * data.camera.distance() * dt);
+        data.camera.pan(Vec3::new(-pan.x, -pan.y, 0.0));
+        true
+    }
+
+    /// World-space positions of the mesh vertices
+    fn world_positions(&self, data: &AppState) -> Vec<Vec3> {
+        self.mesh
+            .positions
+            .iter()
+            .map(|&position| data.model_to_world(position))
+            .collect()
+    }
+
+    /// Animates the camera to fit the given vertices, or the whole mesh if there are none
+    fn frame(&mut self, data: &AppState, vertices: &[usize]) {
+        let positions = self.world_positions(data);
+        let points: Vec<Vec3> = if vertices.is_empty() {
+            positions
+        } else {
+            vertices.iter().map(|&index| positions[index]).collect()
+        };
+        if let Some(sphere) = BoundingSphere::from_points(&points) {
+            let to = data
+                .camera
+                .framing(sphere, data.min_distance, data.max_distance);
+            self.start_transition(CameraTransition::new(data.camera, to));
+        }
+    }
+
+    /// Starts an animated camera move, stopping any zoom or orbit still in motion
+    fn start_transition(&mut self, transition: CameraTransition) {
+        self.pending_zoom = 0.0;
+        self.orbit_velocity = Vec3::ZERO;
+        self.camera_transition = Some(transition);
+    }
 
-        // Rotation matrices
-        let (sin_x, cos_x) = data.angle_x.sin_cos();
-        let (sin_y, cos_y) = data.angle_y.sin_cos();
+    /// Keeps orbiting with the velocity of the last drag, slowing down over time
+    fn coast(&mut self, data: &mut AppState, dt: f64) {
+        let speed = self.orbit_velocity.length();
+        data.camera
+            .orbit(Quat::from_axis_angle(self.orbit_velocity, speed * dt));
+        self.orbit_velocity *= (-data.orbit_damping * dt).exp();
+        if self.orbit_velocity.length() < MIN_COAST_SPEED {
+            self.orbit_velocity = Vec3::ZERO;
+        }
+    }
+
+    /// World-space ray from the camera through a screen point
+    fn cursor_ray(&self, data: &AppState, pos: Point) -> Ray {
+        let viewport = self.viewport(data);
+        let screen = Vec2::new(pos.x, pos.y);
+        // Depth 0.0 is the eye under perspective and the camera plane under orthographic
+        let origin = viewport.unproject(screen, 0.0);
+        let through = viewport.unproject(screen, 1.0);
+        Ray::through(
+            data.camera.view_to_world(origin),
+            data.camera.view_to_world(through),
+        )
+    }
+
+    /// Returns the visible face under a screen point and the world-space point on it.
+    ///
+    /// Reads the ID buffer of the last frame, and casts a ray instead if there is
+    /// none for the current window size.
+    fn surface_under_cursor(&self, data: &AppState, pos: Point) -> Option<(usize, Vec3)> {
+        match &self.id_buffer {
+            Some(ids)
+                if ids.width == self.size.width as usize
+                    && ids.height == self.size.height as usize =>
+            {
+                if pos.x < 0.0 || pos.y < 0.0 {
+                    return None;
+                }
+                let (id, depth) = ids.get(pos.x as usize, pos.y as usize)?;
+                let view = self
+                    .viewport(data)
+                    .unproject(Vec2::new(pos.x, pos.y), depth);
+                Some((id as usize, data.camera.view_to_world(view)))
+            }
+            _ => {
+                let ray = self.cursor_ray(data, pos);
+                let hit = pick(&ray, &self.mesh, &self.world_positions(data))?;
+                Some((hit.face, hit.position))
+            }
+        }
+    }
 
-        let rotation_x = [[1.0, 0.0, 0.0], [0.0, cos_x, -sin_x], [0.0, sin_x, cos_x]];
+    /// Returns the element of the current selection kind under the cursor, if any
+    fn element_under_cursor(&self, data: &AppState, pos: Point) -> Option<usize> {
+        let (face, point) = self.surface_under_cursor(data, pos)?;
+        let positions = self.world_positions(data);
+        Some(
+            data.selection_mode
+                .element_at(&self.mesh, &positions, face, point),
+        )
+    }
 
-        let rotation_y = [[cos_y, 0.0, sin_y], [0.0, 1.0, 0.0], [-sin_y, 0.0, cos_y]];
+    /// Masks the visible outline of mesh elements, traced along face borders in the ID buffer
+    fn outline_elements(
+        &self,
+        data: &AppState,
+        ids: &IdBuffer,
+        vertices: &[Vertex],
+        elements: &[usize],
+        width: usize,
+    ) -> Vec<bool> {
+        let viewport = self.viewport(data);
+        let positions = self.world_positions(data);
+        ids.outline(elements, width, |x, y, face, depth| {
+            let screen = Vec2::new(x as f64 + 0.5, y as f64 + 0.5);
+            let point = data.camera.view_to_world(viewport.unproject(screen, depth));
+            let element =
+                data.selection_mode
+                    .element_at(&self.mesh, &positions, face as usize, point);
+            // Only the borders right around a vertex outline it
+            if data.selection_mode == SelectionMode::Vertex {
+                let vertex = &vertices[element];
+                if vertex.position.z < NEAR_PLANE
+                    || (vertex.screen_position - screen).length() > VERTEX_OUTLINE_RADIUS
+                {
+                    return None;
+                }
+            }
+            Some(element)
+        })
+    }
 
-        // Combine rotations
-        let rotation_matrix = multiply_matrices(&rotation_y, &rotation_x);
+    /// Draws line `index` of the debug overlay, counted from the top
+    fn draw_debug_line(&self, ctx: &mut PaintCtx, index: usize, text: &str) {
+        let text_layout = ctx
+            .text()
+            .new_text_layout(text.to_string())
+            .font(FontFamily::SYSTEM_UI, 12.0)
+            .text_color(Color::WHITE)
+            .build()
+            .unwrap();
+        let y = DEBUG_MARGIN + DEBUG_LINE_HEIGHT * index as f64;
+        ctx.draw_text(&text_layout, (DEBUG_MARGIN, y));
+    }
 
-        // Transform and project vertices
-        let transformed_vertices: Vec<[f64; 3]> = vertices
+    /// Returns whether no drag, key or animation is still changing the state
+    fn is_settled(&self) -> bool {
+        let moving = self
+            .held_actions
             .iter()
-            .map(|&(x, y, z)| {
-                let rotated = multiply_matrix_vector(&rotation_matrix, &[x, y, z]);
-                // Apply translation in 3D space
-                [
-                    rotated[0] + data.translation[0] / scale,
-                    rotated[1] + data.translation[1] / scale,
-                    rotated[2],
-                ]
+            .any(|action| !matches!(action, Action::ConstrainAxis(_)));
+        !self.dragging_orbit
+            && !self.dragging_pan
+            && self.gizmo_drag.is_none()
+            && !self.dragging_light
+            && !self.orbiting_light
+            && !self.panel_active
+            && self.camera_transition.is_none()
+            && self.orbit_velocity == Vec3::ZERO
+            && self.pending_zoom == 0.0
+            && !moving
+    }
+
+    /// Lays out the gizmo over the cube's center, sized to a fixed number of pixels
+    fn gizmo(&self, data: &AppState) -> Option<Gizmo> {
+        let viewport = self.viewport(data);
+        let view = data.camera.view_matrix();
+        let project = |point: Vec3| {
+            let position = view.transform_point(point);
+            (position.z >= NEAR_PLANE).then(|| (viewport.project(position), position.z))
+        };
+        let (center, depth) = project(data.position)?;
+        let pixel = viewport.unproject(center + Vec2::new(1.0, 0.0), depth)
+            - viewport.unproject(center, depth);
+        Gizmo::new(
+            data.gizmo,
+            data.position,
+            GIZMO_SIZE * pixel.length(),
+            project,
+        )
+    }
+
+    /// Returns the gizmo handle a left drag starting at `pos` would move, if any.
+    ///
+    /// While an axis key is held, drags anywhere are constrained to that axis.
+    fn gizmo_handle_at(&self, data: &AppState, pos: Point) -> Option<Handle> {
+        let gizmo = self.gizmo(data)?;
+        let axis = self.held_actions.iter().find_map(|action| match action {
+            Action::ConstrainAxis(axis) => Some(*axis),
+            _ => None,
+        });
+        match axis {
+            Some(axis) => data.gizmo.constrained_handle(axis),
+            None => gizmo.handle_at(Vec2::new(pos.x, pos.y)),
+        }
+    }
+
+    /// Draws the gizmo's handles, highlighting the one that is hovered or dragged
+    fn draw_gizmo(&self, ctx: &mut PaintCtx, gizmo: &Gizmo, highlighted: Option<Handle>) {
+        for (handle, polyline) in gizmo.handles() {
+            let color = match handle {
+                _ if Some(*handle) == highlighted => Color::rgb8(255, 255, 0),
+                Handle::Axis(0) | Handle::Ring(0) => Color::rgb8(230, 60, 60),
+                Handle::Axis(1) | Handle::Ring(1) => Color::rgb8(60, 200, 60),
+                Handle::Axis(_) | Handle::Ring(_) => Color::rgb8(70, 110, 240),
+                Handle::Scale => Color::WHITE,
+            };
+            let mut path = BezPath::new();
+            for (i, point) in polyline.iter().enumerate() {
+                let point = Point::new(point.x, point.y);
+                if i == 0 {
+                    path.move_to(point);
+                } else {
+                    path.line_to(point);
+                }
+            }
+            ctx.stroke(path, &color, 3.0);
+            // Arrow heads mark the positive end of the translation axes
+            if let (Handle::Axis(_), [.., end]) = (handle, polyline.as_slice()) {
+                ctx.fill(Circle::new(Point::new(end.x, end.y), 5.0), &color);
+            }
+        }
+    }
+
+    /// Screen position and view depth of the light, if it is in front of the camera
+    fn light_on_screen(&self, data: &AppState) -> Option<(Vec2, f64)> {
+        let position = data
+            .camera
+            .view_matrix()
+            .transform_point(data.light_position);
+        (position.z >= NEAR_PLANE).then(|| (self.viewport(data).project(position), position.z))
+    }
+
+    /// Returns whether the light gizmo is under a screen point
+    fn light_under_cursor(&self, data: &AppState, pos: Point) -> bool {
+        data.show_light
+            && self.light_on_screen(data).is_some_and(|(center, _)| {
+                (Vec2::new(pos.x, pos.y) - center).length() <= LIGHT_SIZE + 4.0
             })
-            .collect();
+    }
 
-        // Compute vertex normals
-        let mut vertex_normals = vec![[0.0; 3]; vertices.len()];
-        let faces = [
-            (0, 1, 2, 3),
-            (5, 4, 7, 6),
-            (4, 0, 3, 7),
-            (1, 5, 6, 2),
-            (4, 5, 1, 0),
-            (3, 2, 6, 7),
+    /// Draws the light as a sun with a line towards the cube's center
+    fn draw_light(&self, ctx: &mut PaintCtx, data: &AppState, highlighted: bool) {
+        let viewport = self.viewport(data);
+        let view = data.camera.view_matrix();
+        let mut light = view.transform_point(data.light_position);
+        let mut center = view.transform_point(data.position);
+        // Clip the line at the near plane, so it points towards a light behind the viewer
+        if light.z < NEAR_PLANE && center.z < NEAR_PLANE {
+            return;
+        }
+        let clip = |from: Vec3, to: Vec3| from.lerp(to, (NEAR_PLANE - from.z) / (to.z - from.z));
+        if light.z < NEAR_PLANE {
+            light = clip(light, center);
+        } else if center.z < NEAR_PLANE {
+            center = clip(center, light);
+        }
+        let (start, end) = (viewport.project(center), viewport.project(light));
+        let color = if highlighted {
+            Color::rgb8(255, 255, 0)
+        } else {
+            LIGHT_COLOR
+        };
+        ctx.stroke(
+            Line::new(Point::new(start.x, start.y), Point::new(end.x, end.y)),
+            &color.with_alpha(0.5),
+            1.0,
+        );
+
+        let Some((sun, _)) = self.light_on_screen(data) else {
+            return;
+        };
+        let sun = Point::new(sun.x, sun.y);
+        ctx.fill(Circle::new(sun, LIGHT_SIZE), &color);
+        for ray in 0..8 {
+            let (sin, cos) = (ray as f64 * std::f64::consts::FRAC_PI_4).sin_cos();
+            let direction = druid::kurbo::Vec2::new(cos, sin);
+            ctx.stroke(
+                Line::new(
+                    sun + direction * (LIGHT_SIZE + 3.0),
+                    sun + direction * (LIGHT_SIZE + 7.0),
+                ),
+                &color,
+                2.0,
+            );
+        }
+    }
+
+    /// Returns the world-space point under the cursor.
+    ///
+    /// This is the nearest surface of the mesh, or the point on the plane through
+    /// the camera target if the cursor is not over it.
+    fn point_under_cursor(&self, data: &AppState, pos: Point) -> Vec3 {
+        match self.surface_under_cursor(data, pos) {
+            Some((_, point)) => point,
+            None => {
+                let view = self
+                    .viewport(data)
+                    .unproject(Vec2::new(pos.x, pos.y), data.camera.distance());
+                data.camera.view_to_world(view)
+            }
+        }
+    }
+
+    /// Applies the next step of the smoothed zoom
+    fn step_zoom(&mut self, data: &mut AppState, dt: f64) {
+        // Exponential smoothing covers a fixed share of the remaining zoom per second
+        let step = if data.zoom_smoothing > 0.0 && self.pending_zoom.abs() > 1e-4 {
+            self.pending_zoom * (1.0 - (-data.zoom_smoothing * dt).exp())
+        } else {
+            self.pending_zoom
+        };
+        let applied = data.camera.zoom(
+            self.zoom_anchor,
+            step.exp(),
+            data.min_distance,
+            data.max_distance,
+        );
+        // Drop the rest of the zoom once a distance limit is reached
+        self.pending_zoom = if (applied.ln() - step).abs() > 1e-9 {
+            0.0
+        } else {
+            self.pending_zoom - step
+        };
+    }
+
+    /// Computes the mapping from view space to the screen for the current state
+    fn viewport(&self, data: &AppState) -> Viewport {
+        // Focal length in pixels for the vertical field of view
+        let focal_length =
+            self.size.height.min(self.size.width) / 2.0 / (FIELD_OF_VIEW / 2.0).tan();
+        // Orthographic views keep the target plane at the size the perspective view shows it
+        let scale = match data.projection {
+            Projection::Perspective => focal_length,
+            Projection::Orthographic => focal_length / data.camera.distance(),
+        };
+        Viewport::new(
+            Vec2::new(self.size.width / 2.0, self.size.height / 2.0),
+            scale,
+            data.projection,
+        )
+    }
+
+    /// Saves the settings once they have stopped changing, or right away with `immediately`.
+    ///
+    /// Called periodically rather than on every change, so that a slider drag or a held
+    /// key writes the file once instead of on every step.
+    fn save_settings(&mut self, data: &AppState, immediately: bool) {
+        let settings = Settings::from_state(data);
+        if self.saved_settings.as_ref() == Some(&settings) {
+            self.pending_settings = None;
+        } else if immediately || self.pending_settings.as_ref() == Some(&settings) {
+            if let Err(err) = settings.save() {
+                eprintln!("Failed to save settings: {}", err);
+            }
+            self.saved_settings = Some(settings);
+            self.pending_settings = None;
+        } else {
+            self.pending_settings = Some(settings);
+        }
+    }
+
+    /// Carries out an action triggered from the keyboard
+    fn perform(&mut self, ctx: &mut EventCtx, data: &mut AppState, action: Action) {
+        match action {
+            Action::ShowHelp => self.show_help(ctx),
+            Action::Quit => {
+                self.save_settings(data, true);
+                // Submit the QUIT_APP command to exit the application
+                ctx.submit_command(commands::QUIT_APP);
+            }
+            Action::ToggleDebug => data.debug = !data.debug,
+            Action::TogglePause => {
+                data.paused = !data.paused;
+                // Reset any mouse events that were captured
+                self.last_mouse_pos = Point::ZERO;
+                self.dragging_orbit = false;
+                self.dragging_pan = false;
+            }
+            Action::ToggleWireframe => data.wireframe = !data.wireframe,
+            Action::SpeedUp => data.scale_angular_velocity(1.25),
+            Action::SlowDown => data.scale_angular_velocity(0.8),
+            Action::IncreaseSpin(axis) => data.step_angular_velocity(axis, KEY_SPIN_STEP),
+            Action::DecreaseSpin(axis) => data.step_angular_velocity(axis, -KEY_SPIN_STEP),
+            Action::CycleRotationMode => data.rotation_mode = data.rotation_mode.next(),
+            Action::CycleSelectionMode => {
+                // Indices of one kind of element mean nothing for another
+                data.selection_mode = data.selection_mode.next();
+                data.selection = Arc::new(Vec::new());
+            }
+            Action::ToggleIds => data.show_ids = !data.show_ids,
+            Action::ToggleLight => data.show_light = !data.show_light,
+            Action::TogglePanel => data.show_panel = !data.show_panel,
+            Action::CycleGizmo => data.gizmo = data.gizmo.next(),
+            Action::FrameAll => self.frame(data, &[]),
+            Action::FrameSelection => {
+                // Frame the vertices of the selected elements
+                let mut vertices: Vec<usize> = data
+                    .selection
+                    .iter()
+                    .flat_map(|&element| data.selection_mode.vertices(&self.mesh, element))
+                    .collect();
+                vertices.sort_unstable();
+                vertices.dedup();
+                self.frame(data, &vertices);
+            }
+            Action::ToggleCameraMode => {
+                data.camera_mode = data.camera_mode.next();
+                self.orbit_velocity = Vec3::ZERO;
+                self.held_actions.clear();
+            }
+            Action::ZoomIn | Action::ZoomOut => {
+                let step = if action == Action::ZoomIn {
+                    -KEY_ZOOM_STEP
+                } else {
+                    KEY_ZOOM_STEP
+                };
+                match data.camera_mode {
+                    CameraMode::Orbit => {
+                        // Zoom towards the center of the view, smoothed like the wheel
+                        self.camera_transition = None;
+                        self.zoom_anchor = data.camera.target;
+                        self.pending_zoom += step;
+                        if data.zoom_smoothing <= 0.0 {
+                            self.step_zoom(data, 0.0);
+                        }
+                    }
+                    CameraMode::Fly => {
+                        data.fly_speed =
+                            (data.fly_speed * (-step).exp()).clamp(MIN_FLY_SPEED, MAX_FLY_SPEED);
+                    }
+                }
+            }
+            Action::ToggleProjection => data.projection = data.projection.next(),
+            Action::CycleTransparency => data.transparency = data.transparency.next(),
+            Action::CycleToneMapping => data.tone_mapping = data.tone_mapping.next(),
+            Action::CyclePalette => data.set_palette(data.palette.next()),
+            Action::DecreaseExposure => data.exposure = (data.exposure / 1.25).max(MIN_EXPOSURE),
+            Action::IncreaseExposure => data.exposure = (data.exposure * 1.25).min(MAX_EXPOSURE),
+            Action::ToggleSsao => data.ssao = !data.ssao,
+            Action::DecreaseSsaoRadius => {
+                data.ssao_radius = (data.ssao_radius / 1.25).max(ssao::MIN_RADIUS)
+            }
+            Action::IncreaseSsaoRadius => {
+                data.ssao_radius = (data.ssao_radius * 1.25).min(ssao::MAX_RADIUS)
+            }
+            Action::DecreaseSsaoStrength => {
+                data.ssao_strength = (data.ssao_strength - 0.1).max(0.0)
+            }
+            Action::IncreaseSsaoStrength => {
+                data.ssao_strength = (data.ssao_strength + 0.1).min(1.0)
+            }
+            Action::CycleBackground => data.background = data.background.next(),
+            Action::CycleFog => data.fog = data.fog.next(),
+            Action::TogglePostEffect(effect) => data.toggle_post_effect(effect),
+            Action::MovePostEffectEarlier(effect) => data.move_post_effect_earlier(effect),
+            Action::View(view) => {
+                // Turn the cube back as well, so the front view shows its front face
+                self.start_transition(
+                    CameraTransition::new(data.camera, view.camera(&data.camera))
+                        .with_orientation(data.orientation, Quat::IDENTITY),
+                );
+            }
+            Action::SaveBookmark(slot) => {
+                data.set_bookmark(Bookmark {
+                    slot,
+                    camera: data.camera,
+                    orientation: data.orientation,
+                    projection: data.projection,
+                });
+            }
+            Action::RestoreBookmark(slot) => {
+                if let Some(bookmark) = data.bookmark(slot) {
+                    data.projection = bookmark.projection;
+                    self.start_transition(
+                        CameraTransition::new(data.camera, bookmark.camera)
+                            .with_orientation(data.orientation, bookmark.orientation),
+                    );
+                }
+            }
+            Action::Undo | Action::Redo => {
+                // Stop anything still moving so it cannot override the restored state
+                self.camera_transition = None;
+                self.orbit_velocity = Vec3::ZERO;
+                self.pending_zoom = 0.0;
+                if action == Action::Undo {
+                    self.history.undo(data);
+                } else {
+                    self.history.redo(data);
+                }
+            }
+            Action::Reset => {
+                // Reset to default values
+                data.orientation = Quat::IDENTITY;
+                data.position = Vec3::ZERO;
+                data.scale = 1.0;
+                data.camera = Camera::default();
+                data.light_position = AppState::default().light_position;
+                self.pending_zoom = 0.0;
+                self.orbit_velocity = Vec3::ZERO;
+                self.camera_transition = None;
+                data.wireframe = false;
+                data.transparency = TransparencyMode::Opaque;
+                data.tone_mapping = AppState::default().tone_mapping;
+                data.exposure = AppState::default().exposure;
+            }
+            // Held actions act while their keys are down, not when pressed
+            Action::ConstrainAxis(_)
+            | Action::MoveForward
+            | Action::MoveBack
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::MoveUp
+            | Action::MoveDown
+            | Action::OrbitLeft
+            | Action::OrbitRight
+            | Action::OrbitUp
+            | Action::OrbitDown
+            | Action::PanLeft
+            | Action::PanRight
+            | Action::PanUp
+            | Action::PanDown => {}
+        }
+        ctx.request_paint();
+    }
+
+    /// Opens a window listing the key bindings and mouse controls
+    fn show_help(&self, ctx: &mut EventCtx) {
+        let program_name = env!("CARGO_PKG_NAME");
+        let program_version = env!("CARGO_PKG_VERSION");
+        let program_authors = env!("CARGO_PKG_AUTHORS");
+
+        let mouse_text: &[&str] = &[
+            " - Left Click: Select (Shift+Click: add/remove)",
+            " - Mouse Left Drag: Orbit camera (fly camera: look around)",
+            " - Mouse Right Drag: Pan camera",
+            " - Drag the sun: Move the light (Alt+Left Drag: orbit it around the cube)",
+            " - Mouse Wheel: Zoom towards the cursor (fly camera: change speed)",
+            "",
         ];
 
-        for &(a, b, c, d) in faces.iter() {
+        // Assemble help text, putting multiple authors (if any) on separate lines
+        let mut help_text = String::from("Controls:\n");
+        for line in self.keymap.help() {
+            help_text.push_str(&line);
+            help_text.push('\n');
+        }
+        for line in mouse_text {
+            help_text.push_str(line);
+            help_text.push('\n');
+        }
+        if !program_authors.is_empty() {
+            help_text.push_str(&format!("Author(s): {}\n", program_authors));
+        }
+        help_text.push_str(&format!("Version: {}\n", program_version));
+
+        struct CloseOnEsc;
+        impl<W: Widget<AppState>> Controller<AppState, W> for CloseOnEsc {
+            fn event(
+                &mut self,
+                child: &mut W,
+                ctx: &mut EventCtx,
+                event: &Event,
+                data: &mut AppState,
+                env: &Env,
+            ) {
+                match event {
+                    Event::WindowConnected => {
+                        ctx.request_focus();
+                    }
+                    Event::KeyDown(key_event) => {
+                        if let druid::keyboard_types::Key::Escape = &key_event.key {
+                            ctx.window().close();
+                            return;
+                        }
+                    }
+                    _ => {}
+                }
+                child.event(ctx, event, data, env);
+            }
+
+            fn lifecycle(
+                &mut self,
+                child: &mut W,
+                ctx: &mut LifeCycleCtx,
+                event: &LifeCycle,
+                data: &AppState,
+                env: &Env,
+            ) {
+                if let LifeCycle::WidgetAdded = event {
+                    ctx.register_for_focus();
+                }
+                child.lifecycle(ctx, event, data, env);
+            }
+        }
+
+        let help_widget = ControllerHost::new(
+            Label::new(help_text).with_text_size(14.0).padding(10.0),
+            CloseOnEsc,
+        );
+
+        let help_window = WindowDesc::new(help_widget)
+            .title(format!("About {}", program_name).to_string())
+            .resizable(false)
+            .window_size((450.0, 300.0));
+
+        ctx.new_window(help_window);
+    }
+
+    /// Computes the projected vertices for the current state
+    fn compute_projected_vertices(&self, data: &AppState) -> Vec<Vertex> {
+        let viewport = self.viewport(data);
+        let model_view = data.camera.view_matrix() * data.model_matrix();
+
+        // Transform vertices into view space
+        let transformed_vertices: Vec<Vec3> = self
+            .mesh
+            .positions
+            .iter()
+            .map(|&vertex| model_view.transform_point(vertex))
+            .collect();
+
+        // Compute vertex normals
+        let mut vertex_normals = vec![Vec3::ZERO; self.mesh.positions.len()];
+        for face in &self.mesh.faces {
             let normal = calculate_normal(
-                &transformed_vertices[a],
-                &transformed_vertices[b],
-                &transformed_vertices[c],
+                transformed_vertices[face[0]],
+                transformed_vertices[face[1]],
+                transformed_vertices[face[2]],
             );
-            for &index in &[a, b, c, d] {
-                vertex_normals[index][0] += normal[0];
-                vertex_normals[index][1] += normal[1];
-                vertex_normals[index][2] += normal[2];
+            for &index in face {
+                vertex_normals[index] += normal;
             }
         }
         for normal in vertex_normals.iter_mut() {
-            let length =
-                (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
-            normal[0] /= length;
-            normal[1] /= length;
-            normal[2] /= length;
+            *normal = normal.normalize();
         }
 
         // Create vertices with normals and screen positions
         let vertices_with_normals: Vec<Vertex> = transformed_vertices
             .iter()
             .zip(vertex_normals.iter())
-            .map(|(&position, &normal)| {
-                let screen_x = position[0] * scale + center.x;
-                let screen_y = position[1] * scale + center.y;
-                Vertex {
-                    position,
-                    screen_position: [screen_x, screen_y],
-                    normal,
-                }
+            .map(|(&position, &normal)| Vertex {
+                position,
+                screen_position: viewport.project(position),
+                normal,
+                w: viewport.w(position),
             })
             .collect();
 
@@ -137,264 +899,292 @@ impl CubeWidget {
 impl Widget<AppState> for CubeWidget {
     /// Handle events for the cube widget
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppState, _env: &Env) {
+        // Every input starts an undoable edit, which lasts until the motion it caused settles
+        if let Event::KeyDown(_) | Event::MouseDown(_) | Event::Wheel(_) = event {
+            self.history.begin(data);
+        }
         match event {
             Event::WindowConnected => {
                 ctx.request_timer(std::time::Duration::from_millis(16));
                 // Request focus to receive keyboard events
                 ctx.request_focus();
             }
+            Event::Command(command) if command.is(EDIT_STARTED) => {
+                // The panel took its snapshot before applying the change
+                if let Some(before) = command.get(EDIT_STARTED) {
+                    self.history.begin_with(before.clone());
+                }
+                self.panel_active = true;
+            }
+            Event::Command(command) if command.is(EDIT_FINISHED) => {
+                self.panel_active = false;
+            }
+            Event::WindowDisconnected => self.save_settings(data, true),
             Event::Timer(_) => {
-                if !data.paused && !self.dragging_rotation && !self.dragging_translation {
-                    data.angle_x += 0.01;
-                    data.angle_y += 0.02;
+                // Movement is scaled by the measured frame time, not the timer interval
+                let now = Instant::now();
+                let dt = now.duration_since(self.last_tick).as_secs_f64().min(0.1);
+                self.last_tick = now;
+                if now.duration_since(self.last_settings_check).as_secs_f64() >= SETTINGS_SAVE_DELAY
+                {
+                    self.last_settings_check = now;
+                    self.save_settings(data, false);
+                }
+                if !data.paused && data.camera_mode == CameraMode::Fly {
+                    self.fly(data, dt);
+                    ctx.request_paint();
+                }
+                if !data.paused && self.steer(data, dt) {
+                    ctx.request_paint();
+                }
+                if let Some(transition) = &mut self.camera_transition {
+                    data.camera = transition.advance(dt);
+                    if let Some(orientation) = transition.orientation() {
+                        data.orientation = orientation;
+                    }
+                    if transition.is_finished() {
+                        self.camera_transition = None;
+                    }
+                    ctx.request_paint();
+                }
+                if !data.paused && !self.dragging_orbit && self.orbit_velocity != Vec3::ZERO {
+                    self.coast(data, dt);
+                    ctx.request_paint();
+                }
+                if !data.paused && self.pending_zoom != 0.0 {
+                    self.step_zoom(data, dt);
+                    ctx.request_paint();
+                }
+                // The cube holds still while a gizmo is shown so it can be manipulated
+                if !data.paused
+                    && data.gizmo == GizmoMode::Off
+                    && !self.dragging_orbit
+                    && !self.dragging_pan
+                    && self.camera_transition.is_none()
+                {
+                    let rotation = Quat::from_euler(data.angular_velocity * dt);
+                    data.rotate(rotation);
+                    self.history.spin(rotation);
                     ctx.request_paint();
                 }
                 ctx.request_timer(std::time::Duration::from_millis(16));
             }
+            Event::KeyUp(key_event) => {
+                for action in self.keymap.held_actions(key_event) {
+                    self.held_actions.remove(&action);
+                }
+            }
             Event::KeyDown(key_event) => {
-                if let druid::keyboard_types::Key::Character(s) = &key_event.key {
-                    match s.as_str() {
-                        "d" | "D" => {
-                            data.debug = !data.debug;
-                            ctx.request_paint();
-                        }
-                        "p" | "P" => {
-                            data.paused = !data.paused;
-                            // Reset any mouse events that were captured
-                            self.last_mouse_pos = Point::ZERO;
-                            self.dragging_rotation = false;
-                            self.dragging_translation = false;
-                            ctx.request_paint();
-                        }
-                        "q" | "Q" => {
-                            // Submit the QUIT_APP command to exit the application
-                            ctx.submit_command(commands::QUIT_APP);
-                        }
-                        "w" | "W" => {
-                            if !data.paused {
-                                data.wireframe = !data.wireframe;
-                                ctx.request_paint();
-                            }
-                        }
-                        "r" | "R" => {
-                            if !data.paused {
-                                // Reset to default values
-                                data.angle_x = 0.0;
-                                data.angle_y = 0.0;
-                                data.translation = [0.0, 0.0];
-                                data.zoom = 1.0;
-                                data.wireframe = false;
-                                ctx.request_paint();
-                            }
-                        }
-                        "h" | "H" => {
-                            let program_name = env!("CARGO_PKG_NAME");
-                            let program_version = env!("CARGO_PKG_VERSION");
-                            let program_authors = env!("CARGO_PKG_AUTHORS");
-
-                            let controls_text: &[&str] = &[
-                                "Controls:",
-                                " - H: Open this help window",
-                                " - Q: Quit the application",
-                                " - D: Toggle debug mode",
-                                " - P: Pause/unpause rotation",
-                                " - W: Toggle wireframe mode",
-                                " - R: Reset cube",
-                                " - Mouse Left Drag: Rotate cube",
-                                " - Mouse Right Drag: Translate cube",
-                                " - Mouse Wheel: Zoom in/out",
-                                "",
-                            ];
-
-                            // Assemble help text, putting multiple authors (if any) on separate lines
-                            let mut help_text = String::new();
-                            for line in controls_text {
-                                help_text.push_str(line);
-                                help_text.push('\n');
-                            }
-                            if !program_authors.is_empty() {
-                                help_text.push_str(&format!("Author(s): {}\n", program_authors));
-                            }
-                            help_text.push_str(&format!("Version: {}\n", program_version));
-
-                            struct CloseOnEsc;
-                            impl<W: Widget<AppState>> Controller<AppState, W> for CloseOnEsc {
-                                fn event(
-                                    &mut self,
-                                    child: &mut W,
-                                    ctx: &mut EventCtx,
-                                    event: &Event,
-                                    data: &mut AppState,
-                                    env: &Env,
-                                ) {
-                                    match event {
-                                        Event::WindowConnected => {
-                                            ctx.request_focus();
-                                        }
-                                        Event::KeyDown(key_event) => {
-                                            if let druid::keyboard_types::Key::Escape =
-                                                &key_event.key
-                                            {
-                                                ctx.window().close();
-                                                return;
-                                            }
-                                        }
-                                        _ => {}
-                                    }
-                                    child.event(ctx, event, data, env);
-                                }
-
-                                fn lifecycle(
-                                    &mut self,
-                                    child: &mut W,
-                                    ctx: &mut LifeCycleCtx,
-                                    event: &LifeCycle,
-                                    data: &AppState,
-                                    env: &Env,
-                                ) {
-                                    if let LifeCycle::WidgetAdded = event {
-                                        ctx.register_for_focus();
-                                    }
-                                    child.lifecycle(ctx, event, data, env);
-                                }
-                            }
-
-                            let help_widget = ControllerHost::new(
-                                Label::new(help_text).with_text_size(14.0).padding(10.0),
-                                CloseOnEsc,
-                            );
-
-                            let help_window = WindowDesc::new(help_widget)
-                                .title(format!("About {}", program_name).to_string())
-                                .resizable(false)
-                                .window_size((450.0, 300.0));
-
-                            ctx.new_window(help_window);
-                        }
-                        _ => {}
+                // Held actions take precedence over presses of the same key while they apply
+                let held: Vec<Action> = self
+                    .keymap
+                    .held_actions(key_event)
+                    .into_iter()
+                    .filter(|action| match action {
+                        Action::ConstrainAxis(_) => data.gizmo != GizmoMode::Off,
+                        Action::MoveForward
+                        | Action::MoveBack
+                        | Action::MoveLeft
+                        | Action::MoveRight
+                        | Action::MoveUp
+                        | Action::MoveDown => data.camera_mode == CameraMode::Fly,
+                        _ => true,
+                    })
+                    .collect();
+                if !held.is_empty() {
+                    self.held_actions.extend(held);
+                } else if let Some(action) = self.keymap.action(key_event) {
+                    // Keep keys such as Tab from moving the keyboard focus
+                    ctx.set_handled();
+                    if !data.paused || action.works_while_paused() {
+                        self.perform(ctx, data, action);
+                    }
+                }
+            }
+            Event::MouseDown(mouse_event) if !data.paused => {
+                self.last_mouse_pos = mouse_event.pos;
+                self.press_pos = mouse_event.pos;
+                // Taking over the camera cancels any animated move
+                self.camera_transition = None;
+                self.orbit_velocity = Vec3::ZERO;
+                self.last_orbit_move = Instant::now();
+                // The camera can be moved from anywhere in the window
+                match mouse_event.button {
+                    druid::MouseButton::Left if mouse_event.mods.alt() => {
+                        self.orbiting_light = true;
+                    }
+                    druid::MouseButton::Left => {
+                        // Gizmo handles and the light take precedence over orbiting
+                        self.gizmo_drag = self.gizmo_handle_at(data, mouse_event.pos);
+                        self.dragging_light = self.gizmo_drag.is_none()
+                            && self.light_under_cursor(data, mouse_event.pos);
+                        self.dragging_orbit = self.gizmo_drag.is_none() && !self.dragging_light;
+                    }
+                    druid::MouseButton::Right => {
+                        self.dragging_pan = true;
                     }
+                    _ => {}
                 }
+                ctx.set_active(true); // Capture mouse events
             }
-            Event::MouseDown(mouse_event) => {
-                if !data.paused {
+            Event::MouseMove(mouse_event) if !data.paused => {
+                if self.cursor_pos != Some(mouse_event.pos) {
+                    // The hovered element is picked when painting
+                    self.cursor_pos = Some(mouse_event.pos);
+                    ctx.request_paint();
+                }
+                let from = Vec2::new(self.last_mouse_pos.x, self.last_mouse_pos.y);
+                let to = Vec2::new(mouse_event.pos.x, mouse_event.pos.y);
+                if self.orbiting_light {
+                    data.light_position =
+                        orbit_light(data.light_position, data.position, &data.camera, to - from);
                     self.last_mouse_pos = mouse_event.pos;
-                    // Compute projected vertices
-                    let vertices_with_normals = self.compute_projected_vertices(data);
-
-                    // Define cube faces (each face is defined by 4 vertex indices)
-                    let faces = [
-                        (0, 1, 2, 3),
-                        (5, 4, 7, 6),
-                        (4, 0, 3, 7),
-                        (1, 5, 6, 2),
-                        (4, 5, 1, 0),
-                        (3, 2, 6, 7),
-                    ];
-
-                    let mut clicked_inside_cube = false;
-                    let click_point = [mouse_event.pos.x, mouse_event.pos.y];
-
-                    for &(a, b, c, d) in &faces {
-                        // Triangle 1: a, b, c
-                        let v0 = &vertices_with_normals[a];
-                        let v1 = &vertices_with_normals[b];
-                        let v2 = &vertices_with_normals[c];
-                        if point_in_triangle(
-                            click_point,
-                            v0.screen_position,
-                            v1.screen_position,
-                            v2.screen_position,
-                        ) {
-                            clicked_inside_cube = true;
-                            break;
-                        }
-                        // Triangle 2: a, c, d
-                        let v0 = &vertices_with_normals[a];
-                        let v1 = &vertices_with_normals[c];
-                        let v2 = &vertices_with_normals[d];
-                        if point_in_triangle(
-                            click_point,
-                            v0.screen_position,
-                            v1.screen_position,
-                            v2.screen_position,
-                        ) {
-                            clicked_inside_cube = true;
-                            break;
-                        }
+                    ctx.request_paint();
+                } else if self.dragging_light {
+                    // Move the light parallel to the screen, keeping it under the cursor
+                    if let Some((_, depth)) = self.light_on_screen(data) {
+                        let viewport = self.viewport(data);
+                        let camera = &data.camera;
+                        data.light_position += camera.view_to_world(viewport.unproject(to, depth))
+                            - camera.view_to_world(viewport.unproject(from, depth));
                     }
-
-                    if clicked_inside_cube {
-                        match mouse_event.button {
-                            druid::MouseButton::Left => {
-                                self.dragging_rotation = true;
-                            }
-                            druid::MouseButton::Right => {
-                                self.dragging_translation = true;
+                    self.last_mouse_pos = mouse_event.pos;
+                    ctx.request_paint();
+                } else if let Some(handle) = self.gizmo_drag {
+                    if let Some(gizmo) = self.gizmo(data) {
+                        match gizmo.drag(handle, from, to) {
+                            GizmoEdit::Translate(offset) => data.position += offset,
+                            GizmoEdit::Rotate(rotation) => data.rotate(rotation),
+                            GizmoEdit::Scale(factor) => {
+                                data.scale = (data.scale * factor).clamp(MIN_SCALE, MAX_SCALE)
                             }
-                            _ => {}
                         }
-                        ctx.set_active(true); // Capture mouse events
                     }
-                }
-            }
-            Event::MouseMove(mouse_event) => {
-                if !data.paused {
-                    if self.dragging_rotation {
-                        let delta = mouse_event.pos - self.last_mouse_pos;
-                        // Update rotation angles based on mouse movement
-                        data.angle_x += delta.y * 0.01; // Adjust sensitivity as needed
-                        data.angle_y += delta.x * 0.01;
-                        self.last_mouse_pos = mouse_event.pos;
-                        ctx.request_paint();
-                    } else if self.dragging_translation {
-                        let delta = mouse_event.pos - self.last_mouse_pos;
-                        // Update translation based on mouse movement
-                        data.translation[0] += delta.x;
-                        data.translation[1] += delta.y;
-                        self.last_mouse_pos = mouse_event.pos;
-                        ctx.request_paint();
+                    self.last_mouse_pos = mouse_event.pos;
+                    ctx.request_paint();
+                } else if self.dragging_orbit && data.camera_mode == CameraMode::Fly {
+                    let delta = to - from;
+                    data.camera
+                        .look(delta.x * LOOK_SENSITIVITY, -delta.y * LOOK_SENSITIVITY);
+                    self.last_mouse_pos = mouse_event.pos;
+                    ctx.request_paint();
+                } else if self.dragging_orbit {
+                    // The arcball sphere fills the window around the camera target
+                    let center = Vec2::new(self.size.width / 2.0, self.size.height / 2.0);
+                    let radius = self.size.width.min(self.size.height) / 2.0;
+                    let up = data.camera.view_matrix().transform_vector(WORLD_UP);
+                    let rotation = data.rotation_mode.drag(from, to, center, radius, up);
+                    data.camera.orbit(rotation);
+                    // Track the drag's angular velocity, smoothing out uneven mouse events
+                    let now = Instant::now();
+                    let elapsed = now.duration_since(self.last_orbit_move).as_secs_f64();
+                    if elapsed > 0.0 {
+                        let (axis, angle) = rotation.to_axis_angle();
+                        self.orbit_velocity =
+                            self.orbit_velocity.lerp(axis * (angle / elapsed), 0.5);
                     }
+                    self.last_orbit_move = now;
+                    self.last_mouse_pos = mouse_event.pos;
+                    ctx.request_paint();
+                } else if self.dragging_pan {
+                    // Keep the point under the cursor on the target plane fixed to the cursor
+                    let viewport = self.viewport(data);
+                    let depth = data.camera.distance();
+                    let offset = viewport.unproject(from, depth) - viewport.unproject(to, depth);
+                    data.camera.pan(offset);
+                    self.last_mouse_pos = mouse_event.pos;
+                    ctx.request_paint();
                 }
             }
-            Event::MouseUp(mouse_event) => {
-                if !data.paused {
-                    match mouse_event.button {
-                        druid::MouseButton::Left => {
-                            self.dragging_rotation = false;
+            Event::MouseUp(mouse_event) if !data.paused => {
+                match mouse_event.button {
+                    druid::MouseButton::Left if self.orbiting_light || self.dragging_light => {
+                        self.orbiting_light = false;
+                        self.dragging_light = false;
+                    }
+                    druid::MouseButton::Left if self.gizmo_drag.is_some() => {
+                        self.gizmo_drag = None;
+                    }
+                    druid::MouseButton::Left => {
+                        self.dragging_orbit = false;
+                        // Coast only if the mouse was still moving when released
+                        let rested = self.last_orbit_move.elapsed().as_secs_f64();
+                        if data.camera_mode == CameraMode::Fly || rested > RELEASE_TIMEOUT {
+                            self.orbit_velocity = Vec3::ZERO;
                         }
-                        druid::MouseButton::Right => {
-                            self.dragging_translation = false;
+                        // A press and release without dragging is a click
+                        if (mouse_event.pos - self.press_pos).hypot() <= CLICK_TOLERANCE {
+                            let element = self.element_under_cursor(data, mouse_event.pos);
+                            data.select(element, mouse_event.mods.shift());
+                            ctx.request_paint();
                         }
-                        _ => {}
                     }
-                    ctx.set_active(false);
+                    druid::MouseButton::Right => {
+                        self.dragging_pan = false;
+                    }
+                    _ => {}
                 }
+                ctx.set_active(false);
             }
-            Event::Wheel(wheel_event) => {
-                if !data.paused {
-                    let delta = wheel_event.wheel_delta.y;
-                    data.zoom *= 1.0 + delta * 0.001;
-                    data.zoom = data.zoom.clamp(0.1, 10.0); // Clamp zoom level
-                    ctx.request_paint();
+            Event::Wheel(wheel_event) if !data.paused => {
+                let delta = wheel_event.wheel_delta.y;
+                match data.camera_mode {
+                    CameraMode::Orbit => {
+                        self.camera_transition = None;
+                        // Zoom towards whatever is under the cursor
+                        self.zoom_anchor = self.point_under_cursor(data, wheel_event.pos);
+                        self.pending_zoom -= delta * ZOOM_SPEED;
+                        if data.zoom_smoothing <= 0.0 {
+                            self.step_zoom(data, 0.0);
+                        }
+                    }
+                    CameraMode::Fly => {
+                        data.fly_speed = (data.fly_speed * (-delta * FLY_WHEEL_SPEED).exp())
+                            .clamp(MIN_FLY_SPEED, MAX_FLY_SPEED);
+                    }
                 }
+                ctx.request_paint();
             }
             _ => {}
         }
+        if self.is_settled() {
+            self.history.end(data);
+        }
     }
 
     fn lifecycle(
         &mut self,
-        _ctx: &mut LifeCycleCtx,
+        ctx: &mut LifeCycleCtx,
         event: &LifeCycle,
-        _data: &AppState,
+        data: &AppState,
         _env: &Env,
     ) {
-        if let LifeCycle::Size(size) = event {
-            self.size = *size;
+        match event {
+            LifeCycle::WidgetAdded => {
+                // The loaded settings need no saving
+                self.saved_settings = Some(Settings::from_state(data));
+            }
+            LifeCycle::Size(size) => {
+                self.size = *size;
+            }
+            LifeCycle::FocusChanged(false) => {
+                // Keys released while another widget or window has the focus never report it
+                self.held_actions.clear();
+            }
+            LifeCycle::HotChanged(false) => {
+                // Nothing is hovered once the cursor leaves the window
+                self.cursor_pos = None;
+                ctx.request_paint();
+            }
+            _ => {}
         }
     }
 
-    fn update(&mut self, _ctx: &mut UpdateCtx, _old_data: &AppState, _data: &AppState, _env: &Env) {
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, _env: &Env) {
+        // Changes made in the settings panel need a repaint even while paused
+        if !old_data.same(data) {
+            ctx.request_paint();
+        }
     }
 
     /// Determines the layout constraints for the cube widget
@@ -427,95 +1217,179 @@ impl Widget<AppState> for CubeWidget {
         let height = size.height as usize;
 
         // Create pixel buffer and z-buffer
-        let mut pixel_data = vec![0u8; width * height * 4];
-        let mut z_buffer = vec![std::f64::INFINITY; width * height];
+        let mut frame = FrameBuffer::new(width, height);
+
+        // Draw the background
+        let background_image = self.load_background_image(&data.background_image);
+        fill_background(
+            &mut frame,
+            data.background,
+            data.background_color,
+            data.background_color_bottom,
+            background_image,
+        );
 
         // Compute projected vertices
         let vertices_with_normals = self.compute_projected_vertices(data);
 
-        // Define cube faces (each face is defined by 4 vertex indices)
-        let faces = [
-            (0, 1, 2, 3),
-            (5, 4, 7, 6),
-            (4, 0, 3, 7),
-            (1, 5, 6, 2),
-            (4, 5, 1, 0),
-            (3, 2, 6, 7),
-        ];
-
-        // Define cube edges (pairs of vertex indices)
-        let edges = [
-            (0, 1),
-            (1, 2),
-            (2, 3),
-            (3, 0), // Front face
-            (4, 5),
-            (5, 6),
-            (6, 7),
-            (7, 4), // Back face
-            (0, 4),
-            (1, 5),
-            (2, 6),
-            (3, 7), // Connecting edges
-        ];
-
         // Define face colors
-        let face_colors = [
-            Color::rgb8(255, 0, 0),   // Red
-            Color::rgb8(0, 255, 0),   // Green
-            Color::rgb8(0, 0, 255),   // Blue
-            Color::rgb8(255, 255, 0), // Yellow
-            Color::rgb8(255, 0, 255), // Magenta
-            Color::rgb8(0, 255, 255), // Cyan
-        ];
+        let face_colors = data.face_colors.map(to_linear);
 
-        // Light source position in world space
+        // Light source position in world space, shaded in view space
         let light_pos_world = data.light_position;
+        let light_pos_view = data.camera.view_matrix().transform_point(light_pos_world);
+
+        // Lighting and fog used to shade every fragment
+        let fog_color = to_linear(data.fog_color);
+        let shading = Shading {
+            light_position: light_pos_view,
+            light_intensity: data.light_intensity,
+            fog: Fog {
+                mode: data.fog,
+                color: [fog_color[0], fog_color[1], fog_color[2]],
+                start: data.fog_start,
+                end: data.fog_end,
+                density: data.fog_density,
+            },
+        };
 
         if data.wireframe {
-            // Draw edges
-            for &(start, end) in &edges {
+            // Draw edges in front of the viewer
+            for (start, end) in self.mesh.edges() {
                 let v0 = &vertices_with_normals[start];
                 let v1 = &vertices_with_normals[end];
-                draw_line(
-                    v0.screen_position[0],
-                    v0.screen_position[1],
-                    v1.screen_position[0],
-                    v1.screen_position[1],
-                    &mut pixel_data,
-                    width,
-                    height,
-                    Color::WHITE,
-                );
+                if let Some((p0, p1)) = clip_line_near(v0, v1) {
+                    draw_line(p0, p1, &mut frame, to_linear(Color::WHITE));
+                }
             }
-        } else {
-            // Draw faces
-            for (face_index, &(a, b, c, d)) in faces.iter().enumerate() {
-                // Triangle 1: a, b, c
-                draw_triangle(
+
+            // Nothing is filled, so rasterize face IDs on their own for picking
+            for (face, [a, b, c]) in self.mesh.triangles() {
+                frame.ids.draw_triangle(
                     &vertices_with_normals[a],
                     &vertices_with_normals[b],
                     &vertices_with_normals[c],
-                    &mut pixel_data,
-                    &mut z_buffer,
-                    width,
-                    height,
-                    &light_pos_world,
-                    face_colors[face_index],
-                );
-                // Triangle 2: a, c, d
-                draw_triangle(
-                    &vertices_with_normals[a],
-                    &vertices_with_normals[c],
-                    &vertices_with_normals[d],
-                    &mut pixel_data,
-                    &mut z_buffer,
-                    width,
-                    height,
-                    &light_pos_world,
-                    face_colors[face_index],
+                    face as u32,
                 );
             }
+        } else {
+            // Split faces into triangles
+            let mut triangles: Vec<([usize; 3], [f64; 4], u32)> = self
+                .mesh
+                .triangles()
+                .into_iter()
+                .map(|(face, t)| (t, face_colors[face % face_colors.len()], face as u32))
+                .collect();
+
+            match data.transparency {
+                TransparencyMode::Opaque => {
+                    // Draw faces
+                    for &([a, b, c], color, face) in &triangles {
+                        draw_triangle(
+                            &vertices_with_normals[a],
+                            &vertices_with_normals[b],
+                            &vertices_with_normals[c],
+                            &mut frame,
+                            &shading,
+                            color,
+                            face,
+                        );
+                    }
+
+                    // Occlude the ambient term of the opaque surfaces
+                    if data.ssao {
+                        let params = SsaoParams {
+                            radius: data.ssao_radius,
+                            strength: data.ssao_strength,
+                        };
+                        apply_ssao(&mut frame, &self.viewport(data), params);
+                    }
+                }
+                TransparencyMode::Sorted => {
+                    // Draw triangles back to front, ordered by centroid depth
+                    let centroid_depth = |t: &[usize; 3]| {
+                        t.iter()
+                            .map(|&i| vertices_with_normals[i].position.z)
+                            .sum::<f64>()
+                    };
+                    triangles.sort_by(|(a, ..), (b, ..)| {
+                        centroid_depth(b).total_cmp(&centroid_depth(a))
+                    });
+                    for &(t, color, face) in &triangles {
+                        let [a, b, c] = front_facing(&vertices_with_normals, t);
+                        draw_triangle_blended(
+                            &vertices_with_normals[a],
+                            &vertices_with_normals[b],
+                            &vertices_with_normals[c],
+                            &mut frame,
+                            &shading,
+                            [color[0], color[1], color[2], data.opacity],
+                            face,
+                        );
+                    }
+                }
+                TransparencyMode::ABuffer => {
+                    // Collect per-pixel fragment lists and resolve them in depth order
+                    let mut abuffer = ABuffer::new(width, height);
+                    for &(t, color, face) in &triangles {
+                        let [a, b, c] = front_facing(&vertices_with_normals, t);
+                        abuffer.draw_triangle(
+                            &vertices_with_normals[a],
+                            &vertices_with_normals[b],
+                            &vertices_with_normals[c],
+                            &shading,
+                            [color[0], color[1], color[2], data.opacity],
+                            face,
+                        );
+                    }
+                    abuffer.resolve(&mut frame);
+                }
+            }
+        }
+
+        // Run the post-processing chain
+        apply_effects(&data.post_effects, &mut frame);
+
+        // Keep the face IDs written by the pass for picking until the next frame
+        let ids = std::mem::replace(&mut frame.ids, IdBuffer::new(0, 0));
+        let mut pixels = if data.show_ids {
+            ids.visualize()
+        } else {
+            frame.encode(data.tone_mapping, data.exposure)
+        };
+        self.id_buffer = Some(ids);
+
+        let gizmo = self.gizmo(data);
+        let gizmo_handle = self.gizmo_drag.or_else(|| {
+            let pos = self.cursor_pos?;
+            gizmo.as_ref()?.handle_at(Vec2::new(pos.x, pos.y))
+        });
+        let light_highlighted = self.dragging_light
+            || self.orbiting_light
+            || (gizmo_handle.is_none()
+                && self
+                    .cursor_pos
+                    .is_some_and(|pos| self.light_under_cursor(data, pos)));
+        let hovered = self
+            .cursor_pos
+            .filter(|_| !self.dragging_orbit && !self.dragging_pan)
+            .filter(|_| gizmo_handle.is_none() && !light_highlighted)
+            .and_then(|pos| self.element_under_cursor(data, pos));
+
+        // Outline the hovered and selected elements where they are visible
+        if let Some(ids) = &self.id_buffer {
+            let hovered: Vec<usize> = hovered.into_iter().collect();
+            let outlines = [
+                (hovered.as_slice(), 0, HOVER_COLOR),
+                (data.selection.as_slice(), 1, SELECTION_COLOR),
+            ];
+            for (elements, width, color) in outlines {
+                if !elements.is_empty() {
+                    let mask =
+                        self.outline_elements(data, ids, &vertices_with_normals, elements, width);
+                    overlay_mask(&mut pixels, &mask, color.as_rgba8());
+                }
+            }
         }
 
         // Create and draw the image
@@ -523,84 +1397,170 @@ impl Widget<AppState> for CubeWidget {
             .make_image(
                 width,
                 height,
-                &pixel_data,
+                &pixels,
                 druid::piet::ImageFormat::RgbaSeparate,
             )
             .unwrap();
         ctx.draw_image(&image, size.to_rect(), InterpolationMode::NearestNeighbor);
+        if data.show_light {
+            self.draw_light(ctx, data, light_highlighted);
+        }
+        if let Some(gizmo) = &gizmo {
+            self.draw_gizmo(ctx, gizmo, gizmo_handle);
+        }
 
         // Add debug info if debug mode is enabled
+        let mut lines = Vec::new();
         if data.debug {
             let text = format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
-            let text_layout = ctx
-                .text()
-                .new_text_layout(text)
-                .font(FontFamily::SYSTEM_UI, 12.0)
-                .text_color(Color::WHITE)
-                .build()
-                .unwrap();
-            ctx.draw_text(&text_layout, (10.0, 10.0));
+            lines.push(text);
 
-            // Draw angles
-            let text = format!("Angle X: {:.2}, Angle Y: {:.2}", data.angle_x, data.angle_y);
-            let text_layout = ctx
-                .text()
-                .new_text_layout(text)
-                .font(FontFamily::SYSTEM_UI, 12.0)
-                .text_color(Color::WHITE)
-                .build()
-                .unwrap();
-            ctx.draw_text(&text_layout, (10.0, 30.0));
+            // Draw orientation as Euler angles
+            let euler = data.orientation.to_euler();
+            let text = format!(
+                "Angle X: {:.2}, Y: {:.2}, Z: {:.2}",
+                euler.x, euler.y, euler.z
+            );
+            lines.push(text);
 
-            // Draw translation
+            // Draw camera
+            let (position, target) = (data.camera.position, data.camera.target);
             let text = format!(
-                "Translation X: {:.2}, Y: {:.2}",
-                data.translation[0], data.translation[1]
+                "Camera: ({:.2}, {:.2}, {:.2}) -> ({:.2}, {:.2}, {:.2})",
+                position.x, position.y, position.z, target.x, target.y, target.z
             );
-            let text_layout = ctx
-                .text()
-                .new_text_layout(text)
-                .font(FontFamily::SYSTEM_UI, 12.0)
-                .text_color(Color::WHITE)
-                .build()
-                .unwrap();
-            ctx.draw_text(&text_layout, (10.0, 50.0));
+            lines.push(text);
 
             // Draw light position
             let text = format!(
                 "Light: ({:.2}, {:.2}, {:.2})",
-                light_pos_world[0], light_pos_world[1], light_pos_world[2]
+                light_pos_world.x, light_pos_world.y, light_pos_world.z
             );
-            let text_layout = ctx
-                .text()
-                .new_text_layout(text)
-                .font(FontFamily::SYSTEM_UI, 12.0)
-                .text_color(Color::WHITE)
-                .build()
-                .unwrap();
-            ctx.draw_text(&text_layout, (10.0, 70.0));
+            lines.push(text);
 
             // Draw FPS
             let text = format!("FPS: {:.2}", self.fps);
-            let text_layout = ctx
-                .text()
-                .new_text_layout(text)
-                .font(FontFamily::SYSTEM_UI, 12.0)
-                .text_color(Color::WHITE)
-                .build()
-                .unwrap();
-            ctx.draw_text(&text_layout, (10.0, 90.0));
+            lines.push(text);
 
-            // Draw zoom level
-            let text = format!("Zoom: {:.2}", data.zoom);
-            let text_layout = ctx
-                .text()
-                .new_text_layout(text)
-                .font(FontFamily::SYSTEM_UI, 12.0)
-                .text_color(Color::WHITE)
-                .build()
-                .unwrap();
-            ctx.draw_text(&text_layout, (10.0, 110.0));
+            // Draw camera distance and projection
+            let text = format!(
+                "Distance: {:.2}, Projection: {}",
+                data.camera.distance(),
+                data.projection.name()
+            );
+            lines.push(text);
+
+            // Draw transparency mode
+            let text = format!(
+                "Transparency: {} (opacity {:.2})",
+                data.transparency.name(),
+                data.opacity
+            );
+            lines.push(text);
+
+            // Draw tone mapping
+            let text = format!(
+                "Tone mapping: {} (exposure {:.2})",
+                data.tone_mapping.name(),
+                data.exposure
+            );
+            lines.push(text);
+
+            // Draw post-processing chain
+            let effects: Vec<&str> = data.post_effects.iter().map(|e| e.name()).collect();
+            let text = if effects.is_empty() {
+                "Effects: none".to_string()
+            } else {
+                format!("Effects: {}", effects.join(" > "))
+            };
+            lines.push(text);
+
+            // Draw ambient occlusion settings
+            let text = format!(
+                "SSAO: {} (radius {:.2}, strength {:.2})",
+                if data.ssao { "on" } else { "off" },
+                data.ssao_radius,
+                data.ssao_strength
+            );
+            lines.push(text);
+
+            // Draw rotation mode
+            let text = format!("Rotation: {}", data.rotation_mode.name());
+            lines.push(text);
+
+            // Draw camera mode
+            let text = format!(
+                "Camera mode: {} (fly speed {:.2})",
+                data.camera_mode.name(),
+                data.fly_speed
+            );
+            lines.push(text);
+
+            // Draw background and fog
+            let text = format!(
+                "Background: {}, Fog: {}",
+                data.background.name(),
+                data.fog.name()
+            );
+            lines.push(text);
+
+            // Draw angular velocity
+            let velocity = data.angular_velocity;
+            let text = format!(
+                "Spin: X {:.2}, Y {:.2}, Z {:.2} rad/s",
+                velocity.x, velocity.y, velocity.z
+            );
+            lines.push(text);
+
+            // Draw the most recently selected element
+            let mode = data.selection_mode;
+            let text = match data.selection.last() {
+                Some(&element) => {
+                    let positions = self.world_positions(data);
+                    let (normal, position) = mode.describe(&self.mesh, &positions, element);
+                    format!(
+                        "Selection: {} {} ({} selected), normal ({:.2}, {:.2}, {:.2}), position ({:.2}, {:.2}, {:.2})",
+                        mode.name(),
+                        element,
+                        data.selection.len(),
+                        normal.x,
+                        normal.y,
+                        normal.z,
+                        position.x,
+                        position.y,
+                        position.z
+                    )
+                }
+                None => format!("Selection: none ({} mode)", mode.name().to_lowercase()),
+            };
+            lines.push(text);
+
+            // Draw gizmo mode and the cube's placement
+            let text = format!(
+                "Gizmo: {}, Position: ({:.2}, {:.2}, {:.2}), Scale: {:.2}",
+                data.gizmo.name(),
+                data.position.x,
+                data.position.y,
+                data.position.z,
+                data.scale
+            );
+            lines.push(text);
+
+            // Draw undo history
+            let (undo, redo) = self.history.len();
+            let text = format!("History: {} undo, {} redo", undo, redo);
+            lines.push(text);
+
+            // Draw face color palette
+            let text = if data.face_colors == data.palette.colors() {
+                format!("Palette: {}", data.palette.name())
+            } else {
+                format!("Palette: {} (edited)", data.palette.name())
+            };
+            lines.push(text);
+        }
+        for (index, text) in lines.iter().enumerate() {
+            self.draw_debug_line(ctx, index, text);
         }
 
         // Display 'Paused' if the simulation is paused
@@ -620,9 +1580,11 @@ impl Widget<AppState> for CubeWidget {
                 .build()
                 .unwrap();
             let text_size = text_layout.size();
+            // Keep clear of the debug overlay in short windows
             let pos = (
                 (size.width - text_size.width) / 2.0,
-                (size.height - text_size.height) / 2.0,
+                ((size.height - text_size.height) / 2.0)
+                    .max(DEBUG_MARGIN + DEBUG_LINE_HEIGHT * lines.len() as f64),
             );
             ctx.draw_text(&text_layout, pos);
         }